@@ -0,0 +1,155 @@
+//! Compare the plain Intcode interpreter with the decode cache.
+//!
+//! ```text
+//! AOC_DAY09=path/to/day09.txt AOC_DAY13=path/to/day13.txt cargo bench --bench intcode
+//! ```
+//!
+//! Puzzle inputs aren't part of the repository, so the day 9 and day 13
+//! workloads only run when their environment variables name an input.
+//! A synthetic workload always runs.
+
+use aoc2019::{
+    intcode::{asm::assemble, Intcode, IntcodeMemory, State, Word},
+    ordering_value, parse, CommaSep,
+};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 10;
+
+struct Workload {
+    name: &'static str,
+    memory: IntcodeMemory,
+    /// drive the computer to completion, returning its answer
+    drive: fn(Intcode) -> Word,
+}
+
+/// sum the integers from the input down to 1
+fn triangle() -> IntcodeMemory {
+    assemble(
+        "
+                in   [n]
+        loop:   add  [total], [n], [total]
+                add  [n], -1, [n]
+                jt   [n], loop
+                out  [total]
+                hlt
+        n:      data 0
+        total:  data 0
+        ",
+    )
+    .unwrap()
+}
+
+fn drive_triangle(mut computer: Intcode) -> Word {
+    computer.push_input(300_000);
+    last_output(computer)
+}
+
+/// day 9 part 2: run BOOST in sensor boost mode
+fn drive_boost(mut computer: Intcode) -> Word {
+    computer.push_input(2);
+    last_output(computer)
+}
+
+/// day 13 part 2: play the arcade game to completion, returning the score
+fn drive_arcade(mut computer: Intcode) -> Word {
+    let (mut ball, mut paddle, mut score) = (0, 0, 0);
+    let mut triple = Vec::with_capacity(3);
+    loop {
+        match computer.resume().unwrap() {
+            State::Halted => return score,
+            State::NeedsInput => computer.push_input(ordering_value(ball.cmp(&paddle)).into()),
+            State::Output(value) => {
+                triple.push(value);
+                if let [x, y, tile] = triple[..] {
+                    match (x, y, tile) {
+                        (-1, 0, _) => score = tile,
+                        (_, _, 3) => paddle = x,
+                        (_, _, 4) => ball = x,
+                        _ => {}
+                    }
+                    triple.clear();
+                }
+            }
+        }
+    }
+}
+
+fn last_output(mut computer: Intcode) -> Word {
+    let mut last = 0;
+    loop {
+        match computer.resume().unwrap() {
+            State::Output(value) => last = value,
+            State::Halted => return last,
+            State::NeedsInput => panic!("unexpected input request"),
+        }
+    }
+}
+
+fn load(variable: &str) -> Option<IntcodeMemory> {
+    let path = std::env::var(variable).ok()?;
+    match parse::<CommaSep<Word>>(Path::new(&path)) {
+        Ok(lines) => Some(lines.flatten().collect()),
+        Err(err) => {
+            println!("could not read {}: {}", path, err);
+            None
+        }
+    }
+}
+
+/// the fastest of several runs, and the answer they computed
+fn measure(workload: &Workload, new: impl Fn(IntcodeMemory) -> Intcode) -> (Duration, Word) {
+    let mut fastest = Duration::from_secs(u64::MAX);
+    let mut answer = None;
+    for _ in 0..ITERATIONS {
+        let computer = new(workload.memory.clone());
+        let start = Instant::now();
+        let result = (workload.drive)(computer);
+        fastest = fastest.min(start.elapsed());
+        assert_eq!(*answer.get_or_insert(result), result, "nondeterministic");
+    }
+    (fastest, answer.unwrap())
+}
+
+fn main() {
+    let mut workloads = vec![Workload {
+        name: "triangle",
+        memory: triangle(),
+        drive: drive_triangle,
+    }];
+    if let Some(memory) = load("AOC_DAY09") {
+        workloads.push(Workload {
+            name: "day09",
+            memory,
+            drive: drive_boost,
+        });
+    }
+    if let Some(mut memory) = load("AOC_DAY13") {
+        // insert quarters
+        memory[0] = 2;
+        workloads.push(Workload {
+            name: "day13",
+            memory,
+            drive: drive_arcade,
+        });
+    }
+
+    println!(
+        "{:<10} {:>14} {:>14} {:>8}",
+        "workload", "interpreter", "decode cache", "speedup"
+    );
+    for workload in &workloads {
+        let (plain, plain_answer) = measure(workload, Intcode::new);
+        let (cached, cached_answer) =
+            measure(workload, |memory| Intcode::new(memory).with_decode_cache());
+        assert_eq!(plain_answer, cached_answer, "engines disagree");
+        println!(
+            "{:<10} {:>14?} {:>14?} {:>7.2}x",
+            workload.name,
+            plain,
+            cached,
+            plain.as_secs_f64() / cached.as_secs_f64()
+        );
+    }
+}
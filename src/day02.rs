@@ -1,5 +1,10 @@
 use crate::{
-    intcode::{compute_intcode, IntcodeMemory, Word},
+    intcode::{
+        compute_intcode,
+        search::{Finished, Search},
+        symbolic::Symbolic,
+        IntcodeMemory, Word,
+    },
     parse, CommaSep, Exercise,
 };
 use std::path::Path;
@@ -24,18 +29,42 @@ impl Exercise for Day02 {
 
         const TARGET: Word = 19_690_720;
 
-        'stop: for noun in 0..=99 {
-            for verb in 0..=99 {
-                let mut memory = initial_memory.clone();
-                memory[1] = noun.into();
-                memory[2] = verb.into();
-                memory = compute_intcode(memory);
-                if memory[0] == TARGET {
-                    println!("100 * noun + verb: {}", (100 * noun) + verb);
-                    break 'stop;
-                }
-            }
+        for (noun, verb) in find_inputs(initial_memory, TARGET) {
+            println!("100 * noun + verb: {}", (100 * noun) + verb);
         }
         println!("done part 2");
     }
 }
+
+/// every noun and verb which leave `target` at address 0
+///
+/// Solves the program's closed form when it is linear in the noun and verb;
+/// otherwise tries every pair.
+fn find_inputs(memory: IntcodeMemory, target: Word) -> Vec<(Word, Word)> {
+    let closed_form = Symbolic::new(&memory)
+        .with_symbol(1, "noun")
+        .with_symbol(2, "verb")
+        .run()
+        .ok()
+        .and_then(|evaluation| evaluation.memory[0].polynomial());
+    if let Some(polynomial) = closed_form {
+        if let Some(solutions) =
+            polynomial.solve_linear(target, &[("noun", 0..=99), ("verb", 0..=99)])
+        {
+            return solutions
+                .into_iter()
+                .map(|solution| (solution[0], solution[1]))
+                .collect();
+        }
+    }
+
+    Search::new(memory, |run: Finished| run.memory[0] == target)
+        .with_cell(1, 0..=99)
+        .with_cell(2, 0..=99)
+        .run()
+        .unwrap()
+        .matches
+        .into_iter()
+        .map(|assignment| (assignment[0], assignment[1]))
+        .collect()
+}
@@ -1,5 +1,5 @@
 use crate::{
-    intcode::{Intcode, IntcodeMemory, Word},
+    intcode::{Arithmetic, Intcode, IntcodeMemory, Word},
     parse, CommaSep, Exercise,
 };
 use std::path::Path;
@@ -9,7 +9,9 @@ pub struct Day;
 impl Exercise for Day {
     fn part1(&self, path: &Path) {
         let memory: IntcodeMemory = parse::<CommaSep<Word>>(path).unwrap().flatten().collect();
-        let mut computer = Intcode::new(memory).using_inputs(&[1]);
+        let mut computer = Intcode::new(memory)
+            .with_arithmetic(Arithmetic::Checked)
+            .using_inputs(&[1]);
         match computer.run_collect() {
             Ok(outputs) => match outputs.len() {
                 0 => println!("no output"),
@@ -28,7 +30,9 @@ impl Exercise for Day {
 
     fn part2(&self, path: &Path) {
         let memory: IntcodeMemory = parse::<CommaSep<Word>>(path).unwrap().flatten().collect();
-        let mut computer = Intcode::new(memory).using_inputs(&[2]);
+        let mut computer = Intcode::new(memory)
+            .with_arithmetic(Arithmetic::Checked)
+            .using_inputs(&[2]);
         let coords = computer
             .run_collect()
             .expect("computation should complete successfully")[0];
@@ -39,6 +43,8 @@ impl Exercise for Day {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::intcode::{IntcodeError, State};
+    use num_bigint::BigInt;
 
     #[test]
     fn test_quine() {
@@ -52,7 +58,8 @@ mod tests {
 
     #[test]
     fn test_16_digits() {
-        let mut computer = Intcode::new(vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0]);
+        let mut computer = Intcode::new(vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0])
+            .with_arithmetic(Arithmetic::Checked);
         let output = computer.run_collect().unwrap();
         assert!(!output.is_empty());
         assert_eq!(output[0].to_string().len(), 16);
@@ -62,8 +69,24 @@ mod tests {
     fn test_big_number() {
         let program = vec![104, 1125899906842624, 99];
         let expect = program[1];
-        let mut computer = Intcode::new(program);
+        let mut computer = Intcode::new(program).with_arithmetic(Arithmetic::Checked);
         let output = computer.run_collect().unwrap();
         assert_eq!(output[0], expect);
     }
+
+    #[test]
+    fn test_overflow() {
+        // square the big number: too big for an i64
+        let program = vec![1002, 5, 1125899906842624, 5, 104, 1125899906842624, 99];
+        let mut computer = Intcode::new(program.clone()).with_arithmetic(Arithmetic::Checked);
+        assert_eq!(
+            computer.run_collect(),
+            Err(IntcodeError::Overflow { ip: 0, word: 1002 })
+        );
+
+        let program: Vec<BigInt> = program.into_iter().map(BigInt::from).collect();
+        let mut computer = Intcode::with_memory(program);
+        let square = BigInt::from(1125899906842624_i64) * BigInt::from(1125899906842624_i64);
+        assert_eq!(computer.resume(), Ok(State::Output(square)));
+    }
 }
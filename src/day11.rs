@@ -1,12 +1,10 @@
 use crate::{
     geometry::{Direction, Point},
-    intcode::{Intcode, IntcodeMemory, Word},
+    intcode::{Intcode, IntcodeMemory, State, Word},
     parse, CommaSep, Exercise,
 };
-use crossbeam_channel::{unbounded as channel, Receiver, Sender};
 use std::collections::HashSet;
 use std::path::Path;
-use std::thread;
 
 pub struct Day;
 
@@ -15,18 +13,8 @@ const HULL_SIZE: usize = 1024;
 impl Exercise for Day {
     fn part1(&self, path: &Path) {
         let memory: IntcodeMemory = parse::<CommaSep<Word>>(path).unwrap().flatten().collect();
-        let (camera, receiver) = channel();
-        let (sender, controls) = channel();
-        let mut computer = Intcode::new(memory)
-            .with_inputs(receiver)
-            .with_outputs(sender);
-        // we may as well send that computer off to do its own thing now;
-        // it'll be ready and waiting to process instructions as soon as
-        // we're ready to tell it its state
-        thread::spawn(move || computer.run());
-
         let mut hull = vec![vec![false; HULL_SIZE]; HULL_SIZE];
-        let mut robot = Robot::new(HULL_SIZE / 2, HULL_SIZE / 2, camera, controls);
+        let mut robot = Robot::new(HULL_SIZE / 2, HULL_SIZE / 2, Intcode::new(memory));
 
         let painted = robot.run(&mut hull).unwrap();
         println!("painted {} hull panels", painted.len());
@@ -34,16 +22,9 @@ impl Exercise for Day {
 
     fn part2(&self, path: &Path) {
         let memory: IntcodeMemory = parse::<CommaSep<Word>>(path).unwrap().flatten().collect();
-        let (camera, receiver) = channel();
-        let (sender, controls) = channel();
-        let mut computer = Intcode::new(memory)
-            .with_inputs(receiver)
-            .with_outputs(sender);
-        thread::spawn(move || computer.run());
-
         let mut hull = vec![vec![false; HULL_SIZE]; HULL_SIZE];
         hull[HULL_SIZE / 2][HULL_SIZE / 2] = true;
-        let mut robot = Robot::new(HULL_SIZE / 2, HULL_SIZE / 2, camera, controls);
+        let mut robot = Robot::new(HULL_SIZE / 2, HULL_SIZE / 2, Intcode::new(memory));
 
         robot.run(&mut hull).unwrap();
 
@@ -89,58 +70,60 @@ impl Exercise for Day {
 pub struct Robot {
     location: Point,
     facing: Direction,
-    camera: Sender<Word>,
-    controls: Receiver<Word>,
+    computer: Intcode,
 }
 
 impl Robot {
-    pub fn new(x: usize, y: usize, camera: Sender<Word>, controls: Receiver<Word>) -> Robot {
+    pub fn new(x: usize, y: usize, computer: Intcode) -> Robot {
         Robot {
             location: Point::new(x as i32, y as i32),
             facing: Direction::Up,
-            camera,
-            controls,
+            computer,
         }
     }
 
     pub fn run(&mut self, hull: &mut Vec<Vec<bool>>) -> Result<HashSet<Point>, String> {
         let mut painted = HashSet::new();
 
-        let mut loc = self.loc()?;
-        let mut existing_color = hull[loc.1][loc.0];
-        while let Ok(_) = self.camera.send(if existing_color { 1 } else { 0 }) {
-            #[cfg(feature = "debug")]
-            dbg!(self.location, existing_color);
+        loop {
+            let loc = self.loc()?;
+            let color_inst = match self.computer.resume().map_err(|err| err.to_string())? {
+                State::Halted => break,
+                State::NeedsInput => {
+                    let existing_color = hull[loc.1][loc.0];
+                    #[cfg(feature = "debug")]
+                    dbg!(self.location, existing_color);
+                    self.computer.push_input(if existing_color { 1 } else { 0 });
+                    continue;
+                }
+                State::Output(color_inst) => color_inst,
+            };
 
-            if let Ok(color_inst) = self.controls.recv() {
-                let new_color = match color_inst {
-                    0 => false,
-                    1 => true,
-                    color => return Err(format!("unexpected paint color: {}", color)),
-                };
+            let new_color = match color_inst {
+                0 => false,
+                1 => true,
+                color => return Err(format!("unexpected paint color: {}", color)),
+            };
 
-                #[cfg(feature = "debug")]
-                dbg!(new_color);
+            #[cfg(feature = "debug")]
+            dbg!(new_color);
 
-                painted.insert(self.location);
+            painted.insert(self.location);
 
-                hull[loc.1][loc.0] = new_color;
-            } else {
-                break;
-            }
+            hull[loc.1][loc.0] = new_color;
 
-            self.facing = match self.controls.recv().expect("if got color must get turn") {
-                0 => self.facing.turn_left(),
-                1 => self.facing.turn_right(),
-                turn => return Err(format!("unexpected turn instruction: {}", turn)),
+            self.facing = match self.computer.resume().map_err(|err| err.to_string())? {
+                State::Output(0) => self.facing.turn_left(),
+                State::Output(1) => self.facing.turn_right(),
+                State::Output(turn) => {
+                    return Err(format!("unexpected turn instruction: {}", turn))
+                }
+                state => return Err(format!("if got color must get turn; got {:?}", state)),
             };
             self.location = self.location + self.facing.deltas();
 
             #[cfg(feature = "debug")]
             dbg!(self.facing);
-
-            loc = self.loc()?;
-            existing_color = hull[loc.1][loc.0];
         }
         Ok(painted)
     }
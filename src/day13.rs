@@ -1,16 +1,17 @@
 use crate::{
     geometry::Point,
-    intcode::{channel, Intcode, IntcodeMemory, Word},
+    intcode::{Intcode, IntcodeMemory, State, Word},
     ordering_value, parse, CommaSep, Exercise,
 };
 use std::convert::{TryFrom, TryInto};
 use std::fmt;
 use std::path::Path;
-use std::thread;
 use term_cursor::{clear, set_pos};
 
 #[cfg(feature = "debug")]
 use std::io::Write;
+#[cfg(feature = "debug")]
+use std::thread;
 
 pub const INFO_X: i32 = 40;
 pub const SCORE_Y: i32 = 3;
@@ -21,21 +22,17 @@ pub struct Day;
 impl Exercise for Day {
     fn part1(&self, path: &Path) {
         let memory: IntcodeMemory = parse::<CommaSep<Word>>(path).unwrap().flatten().collect();
-        let (output, to_screen) = channel();
-        let mut computer = Intcode::new(memory).with_outputs(output);
-        thread::spawn(move || {
-            computer.run().unwrap();
-        });
+        let mut computer = Intcode::new(memory);
 
         clear().unwrap();
         let mut blocks = 0;
         let mut max_y = 0;
-        while let Ok(x) = to_screen.recv() {
-            let y = to_screen.recv().unwrap();
+        while let Some(x) = next_output(&mut computer) {
+            let y = next_output(&mut computer).unwrap();
             if y > max_y {
                 max_y = y;
             }
-            let tile: Tile = to_screen.recv().unwrap().try_into().unwrap();
+            let tile: Tile = next_output(&mut computer).unwrap().try_into().unwrap();
             if tile == Tile::Block {
                 blocks += 1;
             }
@@ -55,27 +52,41 @@ impl Exercise for Day {
     fn part2(&self, path: &Path) {
         let mut memory: IntcodeMemory = parse::<CommaSep<Word>>(path).unwrap().flatten().collect();
         memory[0] = 2;
-        let (outputs, to_screen) = channel();
-        let (joystick, inputs) = channel();
-        let mut computer = Intcode::new(memory)
-            .with_outputs(outputs)
-            .with_inputs(inputs);
-        // run the computer
-        thread::spawn(move || {
-            computer.run().unwrap();
-        });
-
-        // handle output in its own thread, so we don't need to worry about
-        // synchronization with the input
+        let mut computer = Intcode::new(memory);
+
         #[cfg(feature = "debug")]
         clear().unwrap();
         let mut score = 0;
-        let mut ball_pos;
+        let mut ball_pos = Point::default();
         let mut paddle_pos = Point::default();
 
-        while let Ok(x) = to_screen.recv() {
-            let y = to_screen.recv().unwrap();
-            let val = to_screen.recv().unwrap();
+        loop {
+            let x = match computer.resume().unwrap() {
+                State::Halted => break,
+                State::NeedsInput => {
+                    // the game only waits for the joystick once per tick, so we
+                    // know where everything is
+                    let movement = ordering_value(ball_pos.x.cmp(&paddle_pos.x));
+                    computer.push_input(movement.into());
+
+                    #[cfg(feature = "debug")]
+                    {
+                        set_pos(INFO_X, DEBUG_Y).unwrap();
+                        print!("ball: {:?}", ball_pos);
+                        set_pos(INFO_X, DEBUG_Y + 1).unwrap();
+                        print!("paddle: {:?}", paddle_pos);
+                        set_pos(INFO_X, DEBUG_Y + 3).unwrap();
+                        print!("joystick: {:2}", movement);
+
+                        std::io::stdout().flush().unwrap();
+                        thread::sleep(std::time::Duration::from_millis(700));
+                    }
+                    continue;
+                }
+                State::Output(x) => x,
+            };
+            let y = next_output(&mut computer).unwrap();
+            let val = next_output(&mut computer).unwrap();
 
             if x == -1 && y == 0 {
                 score = val;
@@ -95,30 +106,6 @@ impl Exercise for Day {
                 match tile {
                     Tile::Ball => {
                         ball_pos = Point::new(x as i32, y as i32);
-
-                        // the ball has to update once every tick, so let's send our inputs here
-
-                        let movement = ordering_value(ball_pos.x.cmp(&paddle_pos.x));
-                        if let Err(err) = joystick.send(movement.into()) {
-                            set_pos(1, paddle_pos.y + 3).unwrap();
-                            println!("joystick send: {}", err);
-                            return;
-                        };
-
-                        #[cfg(feature = "debug")]
-                        {
-                            set_pos(INFO_X, DEBUG_Y).unwrap();
-                            print!("ball: {:?}", ball_pos);
-                            set_pos(INFO_X, DEBUG_Y + 1).unwrap();
-                            print!("paddle: {:?}", paddle_pos);
-                            // set_pos(INFO_X, DEBUG_Y + 2).unwrap();
-                            // print!("isect: {:?}", isect);
-                            set_pos(INFO_X, DEBUG_Y + 3).unwrap();
-                            print!("joystick: {:2}", movement);
-
-                            std::io::stdout().flush().unwrap();
-                            thread::sleep(std::time::Duration::from_millis(700));
-                        }
                     }
                     Tile::Paddle => {
                         paddle_pos = Point::new(x as i32, y as i32);
@@ -133,6 +120,15 @@ impl Exercise for Day {
     }
 }
 
+/// the next output from the computer, or `None` once it halts
+fn next_output(computer: &mut Intcode) -> Option<Word> {
+    match computer.resume().unwrap() {
+        State::Output(value) => Some(value),
+        State::Halted => None,
+        State::NeedsInput => panic!("arcade cabinet unexpectedly wants input"),
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Tile {
     Empty,
@@ -1,14 +1,12 @@
 use crate::{
     ddbg,
     geometry::{Direction, Map as GenericMap, Point, Traversable},
-    intcode::{channel, Intcode, IntcodeMemory, Word},
+    intcode::{Intcode, IntcodeMemory, State, Word},
     parse, CommaSep, Exercise,
 };
-use crossbeam_channel::{Receiver, Sender};
 use std::cmp::Ordering;
 use std::collections::VecDeque;
 use std::path::Path;
-use std::thread;
 
 const MAP_DIMENSION: usize = 128;
 
@@ -17,17 +15,7 @@ pub struct Day;
 impl Day {
     fn find_target_with_droid(path: &Path) -> Droid {
         let memory: IntcodeMemory = parse::<CommaSep<Word>>(path).unwrap().flatten().collect();
-        let (controller, inputs) = channel();
-        let (outputs, sensor) = channel();
-
-        let mut computer = Intcode::new(memory)
-            .with_inputs(inputs)
-            .with_outputs(outputs);
-        thread::spawn(move || {
-            computer.run().unwrap();
-        });
-
-        let mut droid = Droid::new(controller, sensor);
+        let mut droid = Droid::new(Intcode::new(memory));
         droid.find_target();
         droid
     }
@@ -116,27 +104,28 @@ struct Droid {
     map: Map,
     origin: Point,
     position: Point,
-    controller: Sender<i64>,
-    sensor: Receiver<i64>,
+    computer: Intcode,
 }
 
 impl Droid {
-    fn new(controller: Sender<i64>, sensor: Receiver<i64>) -> Self {
+    fn new(computer: Intcode) -> Self {
         let origin = Point::new((MAP_DIMENSION / 2) as i32, (MAP_DIMENSION / 2) as i32);
         let mut droid = Droid {
             map: Map::new(MAP_DIMENSION, MAP_DIMENSION),
             origin,
             position: origin,
-            controller,
-            sensor,
+            computer,
         };
         droid.map[droid.position] = MapTile::Empty;
         droid
     }
 
     fn go(&mut self, direction: Direction) -> Status {
-        self.controller.send(movement_command(direction)).unwrap();
-        let status: Status = self.sensor.recv().unwrap().into();
+        self.computer.push_input(movement_command(direction));
+        let status: Status = match self.computer.resume().unwrap() {
+            State::Output(status) => status.into(),
+            state => unreachable!("droid must report status after moving; got {:?}", state),
+        };
         let destination_tile = self.position + direction.deltas();
         match (self.map[destination_tile], status) {
             (MapTile::Wall, _) => unreachable!("should never intentionally drive into a wall"),
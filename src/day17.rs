@@ -1,5 +1,5 @@
 use crate::{
-    intcode::{Intcode, IntcodeMemory, Word},
+    intcode::{ascii::AsciiComputer, Intcode, IntcodeMemory, Word},
     parse, CommaSep, Exercise,
 };
 use std::path::Path;
@@ -10,32 +10,24 @@ impl Exercise for Day {
     fn part1(&self, path: &Path) {
         let memory: IntcodeMemory = parse::<CommaSep<Word>>(path).unwrap().flatten().collect();
 
-        let mut computer = Intcode::new(memory);
-        let ascii_data = computer
-            .run_collect()
-            .unwrap()
-            .iter()
-            .map(|c| *c as u8 as char)
-            .collect::<String>();
-        println!("{}", ascii_data);
+        let mut computer = AsciiComputer::new(Intcode::new(memory));
+        let camera = computer.run().unwrap();
+        println!("{}", camera.text());
 
-        let mut data = Vec::new();
-        let mut current_row = Vec::new();
-        for ch in ascii_data.chars() {
-            match ch {
-                '.' => current_row.push(Tile::Empty),
-                '#' | 'v' | '<' | '^' | '>' => current_row.push(Tile::Scaffolding),
-                '\n' => {
-                    let len = current_row.len();
-                    data.push(current_row);
-                    current_row = Vec::with_capacity(len);
-                }
-                _ => unreachable!("unexpected symbol in ascii data"),
-            }
-        }
-        if !current_row.is_empty() {
-            data.push(current_row);
-        }
+        let data: Vec<Vec<Tile>> = camera
+            .lines
+            .iter()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                line.chars()
+                    .map(|ch| match ch {
+                        '.' => Tile::Empty,
+                        '#' | 'v' | '<' | '^' | '>' => Tile::Scaffolding,
+                        _ => unreachable!("unexpected symbol in ascii data"),
+                    })
+                    .collect()
+            })
+            .collect();
 
         let mut alignment_params = 0;
         for (y, row) in data.iter().enumerate() {
@@ -58,24 +50,12 @@ impl Exercise for Day {
             memory
         };
 
-        let inputs = PART_2_MOVEMENT_LOGIC
-            .as_bytes()
-            .iter()
-            .map(|b| *b as Word)
-            .collect::<Vec<_>>();
-
-        let mut computer = Intcode::new(memory).using_inputs(&inputs);
-        let output = computer.run_collect().unwrap();
-        let (other_output, space_dust) = output.split_at(output.len() - 1);
-        let space_dust = space_dust[0];
-        if cfg!(featur = "debug") {
-            println!(
-                "{}",
-                other_output
-                    .iter()
-                    .map(|c| *c as u8 as char)
-                    .collect::<String>()
-            );
+        let mut computer = AsciiComputer::new(Intcode::new(memory));
+        computer.send_lines(PART_2_MOVEMENT_LOGIC.lines()).unwrap();
+        let output = computer.run().unwrap();
+        let space_dust = output.values[0];
+        if cfg!(feature = "debug") {
+            println!("{}", output.text());
         }
         println!("dust collected: {}", space_dust);
     }
@@ -1,6 +1,34 @@
+pub mod amplifiers;
+pub mod aot;
+pub mod ascii;
+pub mod asm;
+pub mod cache;
+pub mod cfg;
+pub mod console;
+pub mod debugger;
+pub mod disasm;
+pub mod fuzz;
+pub mod isa;
+pub mod lint;
+pub mod memory;
+pub mod network;
+pub mod profile;
+pub mod search;
+pub mod symbolic;
+pub mod topology;
+pub mod trace;
+pub mod undo;
+pub mod word;
+
 pub use crossbeam_channel::unbounded as channel;
-use crossbeam_channel::{Receiver, Sender};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+pub use isa::InstructionSet;
+use isa::{Effect, Fault};
+pub use memory::{Memory, PagedMemory};
+use std::collections::VecDeque;
 use std::convert::TryFrom;
+use std::fmt;
+pub use word::{Arithmetic, IntcodeWord};
 
 pub type Word = i64;
 pub type IntcodeMemory = Vec<Word>;
@@ -26,32 +54,265 @@ impl TryFrom<Word> for Mode {
     }
 }
 
+/// Everything which can go wrong while running an Intcode program.
+///
+/// `ip` is the address of the instruction which failed, and `word` is
+/// the instruction word found there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntcodeError<W = Word> {
+    InvalidOpcode {
+        ip: usize,
+        word: W,
+    },
+    InvalidMode {
+        ip: usize,
+        word: W,
+    },
+    IpOverran {
+        ip: usize,
+    },
+    /// no input arrived in time, or no input stream was set
+    InputStarved {
+        ip: usize,
+        word: W,
+    },
+    InputDisconnected {
+        ip: usize,
+        word: W,
+    },
+    /// there was no output stream set, or its receiver hung up
+    OutputWithoutSink {
+        ip: usize,
+        word: W,
+        value: W,
+    },
+    ImmediateWrite {
+        ip: usize,
+        word: W,
+    },
+    NegativeAddress {
+        ip: usize,
+        word: W,
+        address: W,
+    },
+    /// the address is too large to index memory
+    AddressTooLarge {
+        ip: usize,
+        word: W,
+        address: W,
+    },
+    /// the instruction budget ran out before this instruction could execute
+    ///
+    /// The machine is otherwise intact: raise the budget to continue.
+    BudgetExhausted {
+        ip: usize,
+    },
+    /// writing to `address` would allocate more memory than the limit allows
+    MemoryLimit {
+        ip: usize,
+        word: W,
+        address: usize,
+    },
+    /// a result didn't fit in a word, under `Arithmetic::Checked`,
+    /// or a relative address didn't fit under any arithmetic
+    Overflow {
+        ip: usize,
+        word: W,
+    },
+    /// the instruction's handler reported a `Fault::Other`
+    HandlerFailed {
+        ip: usize,
+        word: W,
+        message: String,
+    },
+}
+
+impl<W> IntcodeError<W> {
+    /// the address of the instruction which caused the error
+    pub fn ip(&self) -> usize {
+        use IntcodeError::*;
+        match self {
+            InvalidOpcode { ip, .. }
+            | InvalidMode { ip, .. }
+            | IpOverran { ip }
+            | InputStarved { ip, .. }
+            | InputDisconnected { ip, .. }
+            | OutputWithoutSink { ip, .. }
+            | ImmediateWrite { ip, .. }
+            | NegativeAddress { ip, .. }
+            | AddressTooLarge { ip, .. }
+            | BudgetExhausted { ip }
+            | MemoryLimit { ip, .. }
+            | Overflow { ip, .. }
+            | HandlerFailed { ip, .. } => *ip,
+        }
+    }
+}
+
+impl<W: fmt::Display> fmt::Display for IntcodeError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use IntcodeError::*;
+        match self {
+            InvalidOpcode { ip, word } => write!(f, "invalid opcode @ {}: {}", ip, word),
+            InvalidMode { ip, word } => write!(f, "invalid parameter mode @ {}: {}", ip, word),
+            IpOverran { ip } => write!(f, "ip overran memory at {}", ip),
+            InputStarved { ip, .. } => write!(f, "input at {} but none was available", ip),
+            InputDisconnected { ip, .. } => {
+                write!(f, "input at {} but the input stream disconnected", ip)
+            }
+            OutputWithoutSink { ip, value, .. } => {
+                write!(f, "output at {} ({}) but no output stream set", ip, value)
+            }
+            ImmediateWrite { ip, word } => write!(
+                f,
+                "attempt to mutate an immediate value at ip {}: {}",
+                ip, word
+            ),
+            NegativeAddress { ip, address, .. } => {
+                write!(f, "negative address at ip {}: {}", ip, address)
+            }
+            AddressTooLarge { ip, address, .. } => {
+                write!(f, "address too large at ip {}: {}", ip, address)
+            }
+            BudgetExhausted { ip } => write!(f, "instruction budget exhausted at ip {}", ip),
+            MemoryLimit { ip, address, .. } => write!(
+                f,
+                "writing address {} at ip {} would exceed the memory limit",
+                address, ip
+            ),
+            Overflow { ip, word } => write!(f, "overflow at ip {}: {}", ip, word),
+            HandlerFailed { ip, word, message } => {
+                write!(f, "instruction failed at ip {} ({}): {}", ip, word, message)
+            }
+        }
+    }
+}
+
+impl<W: fmt::Debug + fmt::Display> std::error::Error for IntcodeError<W> {}
+
+/// The reason `Intcode::resume` returned control to its caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State<W = Word> {
+    /// the program executed an input instruction, but no input was pending
+    ///
+    /// Supply some with `push_input`, then resume.
+    NeedsInput,
+    Output(W),
+    Halted,
+}
+
+/// The memory cells which an instruction reads and writes.
+///
+/// This excludes the cells of the instruction itself.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Accesses {
+    pub reads: Vec<usize>,
+    pub write: Option<usize>,
+}
+
+/// The complete state of an `Intcode` machine, excluding its channels.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Snapshot<M: Memory = IntcodeMemory> {
+    pub ip: usize,
+    pub memory: M,
+    pub halted: bool,
+    pub relative_base_offset: M::Word,
+    pub pending_inputs: VecDeque<M::Word>,
+}
+
+/// An Intcode machine, storing its memory in `M`.
 #[derive(Debug, Default)]
-pub struct Intcode {
+pub struct Intcode<M: Memory = IntcodeMemory> {
     ip: usize,
-    memory: IntcodeMemory,
+    memory: M,
     halted: bool,
-    inputs: Option<Receiver<Word>>,
-    outputs: Option<Sender<Word>>,
+    inputs: Option<Receiver<M::Word>>,
+    outputs: Option<Sender<M::Word>>,
     output_ips: Option<Sender<usize>>,
     halts: Option<Sender<usize>>,
-    relative_base_offset: usize,
+    relative_base_offset: M::Word,
+    pending_inputs: VecDeque<M::Word>,
+    arithmetic: Arithmetic,
+    instructions: InstructionSet<M::Word>,
+    decode_cache: Option<cache::DecodeCache<M::Word>>,
+    undo_log: Option<undo::UndoLog<M::Word>>,
+    trace: Option<Sender<trace::TraceEvent<M::Word>>>,
+    profile: Option<profile::Profile>,
+    /// input consumed or output produced by the instruction last executed
+    io: Option<undo::Io<M::Word>>,
+    /// quantity of instructions which have retired
+    retired: u64,
+    /// value of `retired` at which execution stops
+    budget: Option<u64>,
+    /// maximum quantity of cells of memory which may be allocated
+    memory_limit: Option<usize>,
 }
 
 impl Intcode {
     pub fn new(memory: IntcodeMemory) -> Intcode {
+        Intcode::with_memory(memory)
+    }
+
+    /// destructure a word into its opcode and triplet of modes
+    ///
+    /// output tuple is (opcode, param1, param2, param3) to line up with the position
+    /// of the parameters.
+    ///
+    /// This doesn't know where the word came from, so errors report an ip of 0.
+    pub fn destructure(word: Word) -> Result<(Opcode, Mode, Mode, Mode), IntcodeError> {
+        let invalid_mode = |_| IntcodeError::InvalidMode { ip: 0, word };
+        if word < 0 {
+            return Err(IntcodeError::InvalidOpcode { ip: 0, word });
+        }
+        let mut digits = word;
+        let opcode = (digits % 100) as Opcode;
+        digits /= 100;
+        let pc = Mode::try_from(digits % 10).map_err(invalid_mode)?;
+        digits /= 10;
+        let pb = Mode::try_from(digits % 10).map_err(invalid_mode)?;
+        digits /= 10;
+        let pa = Mode::try_from(digits % 10).map_err(invalid_mode)?;
+        digits /= 10;
+        if digits == 0 {
+            Ok((opcode, pc, pb, pa))
+        } else {
+            Err(IntcodeError::InvalidMode { ip: 0, word })
+        }
+    }
+}
+
+impl<M: Memory> Intcode<M> {
+    pub fn with_memory(memory: M) -> Self {
         Intcode {
             memory,
             ..Intcode::default()
         }
     }
 
-    pub fn with_inputs(mut self, inputs: Receiver<Word>) -> Self {
+    /// fail instead of allocating more than `limit` cells of memory
+    pub fn with_memory_limit(mut self, limit: usize) -> Self {
+        self.memory_limit = Some(limit);
+        self
+    }
+
+    /// choose what happens when arithmetic overflows
+    pub fn with_arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.arithmetic = arithmetic;
+        self
+    }
+
+    /// understand the instructions of `instructions` instead of the standard set
+    pub fn with_instruction_set(mut self, instructions: InstructionSet<M::Word>) -> Self {
+        self.instructions = instructions;
+        self
+    }
+
+    pub fn with_inputs(mut self, inputs: Receiver<M::Word>) -> Self {
         self.inputs = Some(inputs);
         self
     }
 
-    pub fn with_outputs(mut self, outputs: Sender<Word>) -> Self {
+    pub fn with_outputs(mut self, outputs: Sender<M::Word>) -> Self {
         self.outputs = Some(outputs);
         self
     }
@@ -68,96 +329,310 @@ impl Intcode {
         self
     }
 
+    /// allow at most `budget` more instructions to retire
+    ///
+    /// Once the budget is exhausted, execution fails with `BudgetExhausted`
+    /// instead of running forever.
+    pub fn with_budget(mut self, budget: u64) -> Self {
+        self.set_budget(Some(budget));
+        self
+    }
+
+    /// allow at most `budget` more instructions to retire, or any quantity if `None`
+    pub fn set_budget(&mut self, budget: Option<u64>) {
+        self.budget = budget.map(|budget| self.retired.saturating_add(budget));
+    }
+
+    /// the quantity of instructions which may yet retire, if there is a budget
+    pub fn remaining_budget(&self) -> Option<u64> {
+        self.budget
+            .map(|budget| budget.saturating_sub(self.retired))
+    }
+
+    /// the quantity of instructions which have retired in this machine's lifetime
+    pub fn retired(&self) -> u64 {
+        self.retired
+    }
+
+    /// capture the machine's state, so it can be restored or forked later
+    pub fn snapshot(&self) -> Snapshot<M> {
+        Snapshot {
+            ip: self.ip,
+            memory: self.memory.clone(),
+            halted: self.halted,
+            relative_base_offset: self.relative_base_offset.clone(),
+            pending_inputs: self.pending_inputs.clone(),
+        }
+    }
+
+    /// return the machine to a previously captured state
+    ///
+    /// Channels are unaffected. Any undo history is discarded.
+    pub fn restore(&mut self, snapshot: &Snapshot<M>) {
+        if let Some(undo_log) = &mut self.undo_log {
+            undo_log.clear();
+        }
+        if let Some(cache) = &mut self.decode_cache {
+            cache.clear();
+        }
+        self.ip = snapshot.ip;
+        self.memory.clone_from(&snapshot.memory);
+        self.halted = snapshot.halted;
+        self.relative_base_offset = snapshot.relative_base_offset.clone();
+        self.pending_inputs.clone_from(&snapshot.pending_inputs);
+    }
+
+    /// queue an input for the program
+    ///
+    /// Queued inputs are consumed before any input stream.
+    pub fn push_input(&mut self, input: M::Word) {
+        self.pending_inputs.push_back(input);
+    }
+
     // convenience fn to initialize with static inputs
-    pub fn using_inputs(self, inputs: &[Word]) -> Self {
+    pub fn using_inputs(self, inputs: &[M::Word]) -> Self {
         let (sender, receiver) = channel();
         for input in inputs {
-            sender.send(*input).unwrap();
+            sender.send(input.clone()).unwrap();
         }
         self.with_inputs(receiver)
     }
 
-    /// destructure a word into its opcode and triplet of modes
-    ///
-    /// output tuple is (opcode, param1, param2, param3) to line up with the position
-    /// of the parameters.
-    pub fn destructure(mut word: Word) -> Result<(Opcode, Mode, Mode, Mode), String> {
-        let opcode = (word % 100) as Opcode;
-        word /= 100;
-        let pc = Mode::try_from(word % 10)?;
-        word /= 10;
-        let pb = Mode::try_from(word % 10)?;
-        word /= 10;
-        let pa = Mode::try_from(word % 10)?;
-        word /= 10;
-        if word == 0 {
-            Ok((opcode, pc, pb, pa))
-        } else {
-            Err(format!(
-                "could not destructure word as opcode: extra digits: {}",
-                word
-            ))
+    /// the address of the next instruction to execute
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> M::Word {
+        self.relative_base_offset.clone()
+    }
+
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    pub fn instruction_set(&self) -> &InstructionSet<M::Word> {
+        &self.instructions
+    }
+
+    /// inputs queued by `push_input` which the program hasn't yet consumed
+    pub fn pending_inputs(&self) -> &VecDeque<M::Word> {
+        &self.pending_inputs
+    }
+
+    /// compute the memory cells which the instruction at the current ip will touch
+    pub fn accesses(&self) -> Result<Accesses, IntcodeError<M::Word>> {
+        if self.ip >= self.memory.len() {
+            return Err(IntcodeError::IpOverran { ip: self.ip });
+        }
+        let (opcode, p1, p2, p3) = self.decode()?;
+        let mnemonic = self
+            .instructions
+            .by_opcode(opcode)
+            .ok_or(IntcodeError::InvalidOpcode {
+                ip: self.ip,
+                word: self.word(),
+            })?;
+        let mut accesses = Accesses::default();
+        for (idx, &mode) in [p1, p2, p3][..mnemonic.arity].iter().enumerate() {
+            if Some(idx) == mnemonic.writes {
+                accesses.write = Some(self.address(idx + 1, mode)?);
+            } else if mode != Mode::Immediate {
+                accesses.reads.push(self.address(idx + 1, mode)?);
+            }
         }
+        Ok(accesses)
     }
 
-    /// get the value indicated by the position in memory at `self.ip + relative`
-    fn mem(&self, relative: usize, mode: Mode) -> Word {
-        let value = self.memory[self.ip + relative];
-        use Mode::*;
-        let idx = match mode {
-            Position => value as usize,
-            Immediate => return value,
-            Relative => (value as i64 + self.relative_base_offset as i64) as usize,
+    /// the instruction word at the current ip
+    fn word(&self) -> M::Word {
+        self.memory.read(self.ip)
+    }
+
+    /// the opcode of the instruction word at the current ip, valid or not
+    fn opcode(&self) -> Opcode {
+        self.word()
+            .to_i64()
+            .map_or(0, |word| (word % 100) as Opcode)
+    }
+
+    /// destructure the instruction word at the current ip
+    fn decode(&self) -> Result<(Opcode, Mode, Mode, Mode), IntcodeError<M::Word>> {
+        let (ip, word) = (self.ip, self.word());
+        let small = match word.to_i64() {
+            Some(small) => small,
+            None => return Err(IntcodeError::InvalidOpcode { ip, word }),
         };
-        if idx >= self.memory.len() {
-            0
-        } else {
-            self.memory[idx]
+        Intcode::destructure(small).map_err(|err| match err {
+            IntcodeError::InvalidMode { .. } => IntcodeError::InvalidMode { ip, word },
+            _ => IntcodeError::InvalidOpcode { ip, word },
+        })
+    }
+
+    /// get the raw value in memory at `self.ip + relative`
+    fn param(&self, relative: usize) -> M::Word {
+        self.memory.read(self.ip + relative)
+    }
+
+    /// interpret a value as an address
+    fn to_address(&self, value: M::Word) -> Result<usize, IntcodeError<M::Word>> {
+        match value.to_i64() {
+            Some(address) if address >= 0 => Ok(address as usize),
+            _ if value < M::Word::default() => Err(IntcodeError::NegativeAddress {
+                ip: self.ip,
+                word: self.word(),
+                address: value,
+            }),
+            _ => Err(IntcodeError::AddressTooLarge {
+                ip: self.ip,
+                word: self.word(),
+                address: value,
+            }),
+        }
+    }
+
+    /// decode the instruction at the current ip, with its raw parameters
+    ///
+    /// Uses the decode cache, if there is one.
+    fn fetch(&mut self) -> Result<cache::Decoded<M::Word>, IntcodeError<M::Word>> {
+        let ip = self.ip;
+        if let Some(decoded) = self.decode_cache.as_mut().and_then(|cache| cache.get(ip)) {
+            return Ok(decoded);
+        }
+        let (opcode, p1, p2, p3) = self.decode()?;
+        let mnemonic = self.instructions.by_opcode(opcode).cloned();
+        let arity = mnemonic.map_or(0, |mnemonic| mnemonic.arity);
+        let mut params: [M::Word; 3] = Default::default();
+        for (idx, param) in params.iter_mut().take(arity).enumerate() {
+            *param = self.param(idx + 1);
+        }
+        let decoded = cache::Decoded {
+            opcode,
+            modes: [p1, p2, p3],
+            params,
+            len: 1 + arity,
+        };
+        if let (Some(cache), Some(_)) = (&mut self.decode_cache, mnemonic) {
+            cache.insert(ip, decoded.clone());
         }
+        Ok(decoded)
+    }
+
+    /// get the address indicated by the position in memory at `self.ip + relative`
+    fn address(&self, relative: usize, mode: Mode) -> Result<usize, IntcodeError<M::Word>> {
+        self.resolve(self.param(relative), mode)
     }
 
-    /// get a mutable ref to the value indicated by the position in memory at `self.ip + relative`
-    fn mem_mut(&mut self, relative: usize, mode: Mode) -> &mut Word {
-        let value = self.memory[self.ip + relative];
+    /// get the address indicated by a raw parameter
+    fn resolve(&self, value: M::Word, mode: Mode) -> Result<usize, IntcodeError<M::Word>> {
         use Mode::*;
-        let idx = match mode {
-            Position => value as usize,
-            Immediate => panic!("attempt to mutate an immediate value at ip {}", self.ip),
-            Relative => (value as i64 + self.relative_base_offset as i64) as usize,
+        let address = match mode {
+            Position => value,
+            Immediate => {
+                return Err(IntcodeError::ImmediateWrite {
+                    ip: self.ip,
+                    word: self.word(),
+                })
+            }
+            Relative => value
+                .checked_add(&self.relative_base_offset)
+                .ok_or_else(|| self.overflow())?,
         };
-        // grow the memory as required to ensure the target is in the vector
-        self.memory
-            .resize_with(std::cmp::max(self.memory.len(), idx + 1), Default::default);
-        &mut self.memory[idx]
-    }
-
-    fn apply3<F>(&mut self, p1: Mode, p2: Mode, p3: Mode, operation: F)
-    where
-        F: FnOnce(Word, Word) -> Word,
-    {
-        let p1v = self.mem(1, p1);
-        let p2v = self.mem(2, p2);
-        *self.mem_mut(3, p3) = operation(p1v, p2v);
-        self.ip += 4;
-    }
-
-    fn jumpif<F>(&mut self, p1: Mode, p2: Mode, condition: F)
-    where
-        F: FnOnce(Word) -> bool,
-    {
-        // jump if condition is true
-        let test = self.mem(1, p1);
-        let ipval = self.mem(2, p2);
-        #[cfg(feature = "intcode-debug")]
-        dbg!("jump-if", self.ip, test, ipval);
-        if condition(test) {
-            self.ip = ipval as usize;
-        } else {
-            self.ip += 3;
+        self.to_address(address)
+    }
+
+    fn overflow(&self) -> IntcodeError<M::Word> {
+        IntcodeError::Overflow {
+            ip: self.ip,
+            word: self.word(),
+        }
+    }
+
+    /// get the value indicated by the position in memory at `self.ip + relative`
+    fn mem(&self, relative: usize, mode: Mode) -> Result<M::Word, IntcodeError<M::Word>> {
+        self.read(self.param(relative), mode)
+    }
+
+    /// get the value indicated by a raw parameter
+    fn read(&self, value: M::Word, mode: Mode) -> Result<M::Word, IntcodeError<M::Word>> {
+        if mode == Mode::Immediate {
+            return Ok(value);
+        }
+        let idx = self.resolve(value, mode)?;
+        Ok(self.memory.read(idx))
+    }
+
+    /// store `value` at the position indicated by a raw parameter
+    fn store(
+        &mut self,
+        param: M::Word,
+        mode: Mode,
+        value: M::Word,
+    ) -> Result<(), IntcodeError<M::Word>> {
+        let idx = self.resolve(param, mode)?;
+        if let Some(limit) = self.memory_limit {
+            if self.memory.allocated_after(idx, &value) > limit {
+                return Err(IntcodeError::MemoryLimit {
+                    ip: self.ip,
+                    word: self.word(),
+                    address: idx,
+                });
+            }
+        }
+        self.memory.write(idx, value);
+        if let Some(cache) = &mut self.decode_cache {
+            cache.invalidate(idx);
+        }
+        Ok(())
+    }
+
+    /// execute a single instruction
+    ///
+    /// When `synchronous`, outputs and input requests which can't be satisfied
+    /// from the pending inputs are returned to the caller instead of using the
+    /// channels. Otherwise, only halts are returned.
+    fn tick(&mut self, synchronous: bool) -> Result<Option<State<M::Word>>, IntcodeError<M::Word>> {
+        let was_halted = self.halted;
+        if !was_halted && self.budget.is_some_and(|budget| self.retired >= budget) {
+            return Err(IntcodeError::BudgetExhausted { ip: self.ip });
+        }
+        let (ip, opcode) = (self.ip, self.opcode());
+        let undo = self
+            .undo_log
+            .as_ref()
+            .map(|_| undo::UndoEntry::before(self));
+        let event = self
+            .trace
+            .as_ref()
+            .and_then(|_| trace::TraceEvent::before(self));
+        self.io = None;
+        let result = self.execute(synchronous);
+        if retired(was_halted, &result) {
+            self.retired += 1;
+            if let Some(profile) = &mut self.profile {
+                profile.record(ip, opcode);
+            }
+            if let Some(entry) = undo {
+                let entry = entry.after(self);
+                if let Some(undo_log) = &mut self.undo_log {
+                    undo_log.push(entry);
+                }
+            }
+            if let (Some(event), Some(trace)) = (event, &self.trace) {
+                if let Err(err) = trace.send(event.after(self)) {
+                    if cfg!(feature = "intcode-debug") {
+                        println!("err sending trace: {}", err);
+                    }
+                }
+            }
         }
+        result
     }
 
-    fn tick(&mut self) -> Result<bool, String> {
+    fn execute(
+        &mut self,
+        synchronous: bool,
+    ) -> Result<Option<State<M::Word>>, IntcodeError<M::Word>> {
         if self.ip >= self.memory.len() {
             #[cfg(feature = "intcode-debug")]
             println!("ip overran memory at {}", self.ip);
@@ -168,54 +643,120 @@ impl Intcode {
                     }
                 };
             }
-            return Err(format!("ip overran memory at {}", self.ip));
+            return Err(IntcodeError::IpOverran { ip: self.ip });
         }
         if self.halted {
-            return Ok(false);
+            return Ok(Some(State::Halted));
+        }
+        let cache::Decoded {
+            opcode,
+            modes,
+            mut params,
+            ..
+        } = self.fetch()?;
+        let (mnemonic, handler) = match self.instructions.get(opcode) {
+            Some((&mnemonic, handler)) => (mnemonic, handler),
+            None => {
+                return Err(IntcodeError::InvalidOpcode {
+                    ip: self.ip,
+                    word: self.word(),
+                })
+            }
+        };
+        let mut operands: [M::Word; 3] = Default::default();
+        let mut quantity = 0;
+        for (idx, &mode) in modes[..mnemonic.arity].iter().enumerate() {
+            if Some(idx) != mnemonic.writes {
+                operands[quantity] = self.read(std::mem::take(&mut params[idx]), mode)?;
+                quantity += 1;
+            }
         }
-        let (opcode, p1, p2, p3) = Self::destructure(self.memory[self.ip])?;
-        match opcode {
-            1 => {
-                // add
-                self.apply3(p1, p2, p3, |a, b| a + b);
+        let effect =
+            handler(&operands[..quantity], self.arithmetic).map_err(|fault| match fault {
+                Fault::Overflow => self.overflow(),
+                Fault::Other(message) => IntcodeError::HandlerFailed {
+                    ip: self.ip,
+                    word: self.word(),
+                    message,
+                },
+            })?;
+
+        let next = self.ip + 1 + mnemonic.arity;
+        let written = mnemonic
+            .writes
+            .map(|idx| (std::mem::take(&mut params[idx]), modes[idx]));
+        let no_written = || IntcodeError::HandlerFailed {
+            ip: self.ip,
+            word: self.word(),
+            message: format!("{} has no parameter to write to", mnemonic.name),
+        };
+        match effect {
+            Effect::Next => self.ip = next,
+            Effect::Store(value) => {
+                let (param, mode) = written.ok_or_else(no_written)?;
+                self.store(param, mode, value)?;
+                self.ip = next;
             }
-            2 => {
-                // mul
-                self.apply3(p1, p2, p3, |a, b| a * b);
+            Effect::Jump(target) => {
+                #[cfg(feature = "intcode-debug")]
+                dbg!("jump", self.ip, &target);
+                self.ip = self.to_address(target)?;
             }
-            3 => {
-                // input
-                if let Some(inputs) = &self.inputs {
-                    #[cfg(not(feature = "unbounded-input-time"))]
-                    let input_result = inputs.recv_timeout(std::time::Duration::new(1, 0));
-                    #[cfg(feature = "unbounded-input-time")]
-                    let input_result = inputs.recv();
-
-                    let input = input_result.map_err(|err| {
-                        self.halted = true;
-                        format!("abort: needed input at ip {} but errored: {}", self.ip, err)
-                    })?;
-                    #[cfg(feature = "intcode-debug")]
-                    println!("input at ip {}: {}", self.ip, input);
-                    *self.mem_mut(1, p1) = input.into();
-                    self.ip += 2;
-                } else {
-                    return Err(format!("input at {} but no input stream set", self.ip));
+            Effect::Input => {
+                let (param, mode) = written.ok_or_else(no_written)?;
+                let word = self.word();
+                let input = match (self.pending_inputs.pop_front(), &self.inputs) {
+                    (Some(input), _) => input,
+                    (None, _) if synchronous => return Ok(Some(State::NeedsInput)),
+                    (None, Some(inputs)) => {
+                        #[cfg(not(feature = "unbounded-input-time"))]
+                        let input_result = inputs.recv_timeout(std::time::Duration::new(1, 0));
+                        #[cfg(feature = "unbounded-input-time")]
+                        let input_result =
+                            inputs.recv().map_err(|_| RecvTimeoutError::Disconnected);
+
+                        let ip = self.ip;
+                        input_result.map_err(|err| {
+                            self.halted = true;
+                            match err {
+                                RecvTimeoutError::Timeout => {
+                                    IntcodeError::InputStarved { ip, word }
+                                }
+                                RecvTimeoutError::Disconnected => {
+                                    IntcodeError::InputDisconnected { ip, word }
+                                }
+                            }
+                        })?
+                    }
+                    (None, None) => {
+                        return Err(IntcodeError::InputStarved { ip: self.ip, word });
+                    }
+                };
+                #[cfg(feature = "intcode-debug")]
+                println!("input at ip {}: {}", self.ip, input);
+                if let Err(err) = self.store(param, mode, input.clone()) {
+                    // don't lose the input: it can be consumed once the error is dealt with
+                    self.pending_inputs.push_front(input);
+                    return Err(err);
                 }
+                self.io = Some(undo::Io::Input(input));
+                self.ip = next;
             }
-            4 => {
-                // output
-                let val = self.mem(1, p1);
+            Effect::Output(val) => {
                 #[cfg(feature = "intcode-debug")]
                 println!("output at ip {}: {}", self.ip, val);
-                if let Some(outputs) = &self.outputs {
-                    outputs.send(val).unwrap();
-                } else {
+                let sent = match &self.outputs {
+                    _ if synchronous => true,
+                    Some(outputs) => outputs.send(val.clone()).is_ok(),
+                    None => false,
+                };
+                if !sent {
                     self.halted = true;
-                    return Err(format!(
-                        "output at {} ({}) but no output stream set",
-                        self.ip, val
-                    ));
+                    return Err(IntcodeError::OutputWithoutSink {
+                        ip: self.ip,
+                        word: self.word(),
+                        value: val,
+                    });
                 }
                 if let Some(oips) = &self.output_ips {
                     if let Err(err) = oips.send(self.ip) {
@@ -224,37 +765,22 @@ impl Intcode {
                         }
                     }
                 }
-                self.ip += 2;
-            }
-            5 => {
-                // jump if true
-                self.jumpif(p1, p2, |test| test != 0);
-            }
-            6 => {
-                // jump if false
-                self.jumpif(p1, p2, |test| test == 0);
-            }
-            7 => {
-                // less than
-                self.apply3(p1, p2, p3, |a, b| if a < b { 1 } else { 0 });
-            }
-            8 => {
-                // equals
-                self.apply3(p1, p2, p3, |a, b| if a == b { 1 } else { 0 });
-            }
-            9 => {
-                // relative base offset
-                let adjustment = self.mem(1, p1);
-                if adjustment > 0 {
-                    self.relative_base_offset += adjustment as usize;
-                } else {
-                    self.relative_base_offset -= (-adjustment) as usize;
+                self.io = Some(undo::Io::Output(val.clone()));
+                self.ip = next;
+                if synchronous {
+                    return Ok(Some(State::Output(val)));
                 }
+            }
+            Effect::AdjustRelativeBase(adjustment) => {
+                self.relative_base_offset = self
+                    .arithmetic
+                    .add(&self.relative_base_offset, &adjustment)
+                    .ok_or_else(|| self.overflow())?;
                 #[cfg(feature = "intcode-debug")]
                 println!("set rbo to {} at {}", self.relative_base_offset, self.ip);
-                self.ip += 2;
+                self.ip = next;
             }
-            99 => {
+            Effect::Halt => {
                 #[cfg(feature = "intcode-debug")]
                 println!("program halt at ip {}", self.ip);
                 self.halted = true;
@@ -265,17 +791,53 @@ impl Intcode {
                         }
                     }
                 }
+                return Ok(Some(State::Halted));
             }
-            _ => {
-                return Err(format!("invalid opcode @ {}: {}", self.ip, opcode));
+        }
+        Ok(None)
+    }
+
+    /// execute a single instruction
+    ///
+    /// Like `resume`, this never uses the input or output streams. It returns
+    /// `None` if the instruction neither produced output, needed an input,
+    /// nor halted.
+    pub fn step(&mut self) -> Result<Option<State<M::Word>>, IntcodeError<M::Word>> {
+        self.tick(true)
+    }
+
+    /// run this computer until it produces an output, needs an input, or halts
+    ///
+    /// This never blocks and never uses the input or output streams, so the
+    /// computer can be driven from the caller's own loop.
+    pub fn resume(&mut self) -> Result<State<M::Word>, IntcodeError<M::Word>> {
+        loop {
+            if let Some(state) = self.tick(true)? {
+                return Ok(state);
             }
         }
-        Ok(true)
+    }
+
+    /// like `resume`, but fail with `BudgetExhausted` if `budget` instructions
+    /// retire without the computer producing output, needing input, or halting
+    ///
+    /// Any budget set with `with_budget` still applies.
+    pub fn run_for(&mut self, budget: u64) -> Result<State<M::Word>, IntcodeError<M::Word>> {
+        let outer = self.budget;
+        let limit = self.retired.saturating_add(budget);
+        self.budget = Some(outer.map_or(limit, |outer| outer.min(limit)));
+        let result = self.resume();
+        self.budget = outer;
+        result
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
     }
 
     // run this computer until program completion
-    pub fn run(&mut self) -> Result<(), String> {
-        while self.tick()? {}
+    pub fn run(&mut self) -> Result<(), IntcodeError<M::Word>> {
+        while !matches!(self.tick(false)?, Some(State::Halted)) {}
         #[cfg(feature = "intcode-debug")]
         println!("intcode run complete");
         Ok(())
@@ -283,14 +845,14 @@ impl Intcode {
 
     // run this computer into program completion,
     // collecting the outputs into a vector
-    pub fn run_collect(&mut self) -> Result<Vec<Word>, String> {
+    pub fn run_collect(&mut self) -> Result<Vec<M::Word>, IntcodeError<M::Word>> {
         let (sender, receiver) = channel();
         self.outputs = Some(sender);
         self.run()?;
         // now we have to drop the sender so that we can collect the results
         // of the receiver. For this to work, we have to replace it with a
         // None value, then manually drop it.
-        let sender = std::mem::replace(&mut self.outputs, None);
+        let sender = self.outputs.take();
         std::mem::drop(sender);
         #[cfg(feature = "intcode-debug")]
         println!("dropped sender in run_collect");
@@ -298,10 +860,53 @@ impl Intcode {
     }
 }
 
+impl<M: Memory> From<Snapshot<M>> for Intcode<M> {
+    fn from(snapshot: Snapshot<M>) -> Intcode<M> {
+        Intcode {
+            ip: snapshot.ip,
+            memory: snapshot.memory,
+            halted: snapshot.halted,
+            relative_base_offset: snapshot.relative_base_offset,
+            pending_inputs: snapshot.pending_inputs,
+            ..Intcode::default()
+        }
+    }
+}
+
+/// whether an instruction ran to completion, given whether the machine was
+/// halted beforehand and the result of executing it
+fn retired<W>(was_halted: bool, result: &Result<Option<State<W>>, IntcodeError<W>>) -> bool {
+    match result {
+        Err(_) | Ok(Some(State::NeedsInput)) => false,
+        Ok(Some(State::Halted)) => !was_halted,
+        _ => true,
+    }
+}
+
+impl<M: Memory> Clone for Intcode<M> {
+    /// clone this machine's state and configuration
+    ///
+    /// The clone keeps the instruction set, arithmetic, memory limit, budget
+    /// and quantity of retired instructions, and whether it caches decoded
+    /// instructions. It shares none of the original's channels, so those
+    /// start unset, as do the profile, undo log and trace.
+    fn clone(&self) -> Intcode<M> {
+        Intcode {
+            instructions: self.instructions.clone(),
+            decode_cache: self.decode_cache.as_ref().map(|_| Default::default()),
+            arithmetic: self.arithmetic,
+            memory_limit: self.memory_limit,
+            budget: self.budget,
+            retired: self.retired,
+            ..Intcode::from(self.snapshot())
+        }
+    }
+}
+
 pub fn compute_intcode(memory: IntcodeMemory) -> IntcodeMemory {
     let mut computer = Intcode::new(memory);
     computer.run().unwrap();
-    std::mem::replace(&mut computer.memory, Vec::new())
+    std::mem::take(&mut computer.memory)
 }
 
 pub fn compute_intcode_ioch(
@@ -309,7 +914,7 @@ pub fn compute_intcode_ioch(
     inputs: Receiver<Word>,
     outputs: Sender<Word>,
     halts: Sender<usize>,
-) -> Result<(), String> {
+) -> Result<(), IntcodeError> {
     let mut computer = Intcode::new(memory)
         .with_inputs(inputs)
         .with_outputs(outputs)
@@ -317,3 +922,271 @@ pub fn compute_intcode_ioch(
 
     computer.run()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume() {
+        let program = asm::assemble(
+            "
+            loop:   in   [x]
+                    jf   [x], end
+                    mul  [x], 2, [x]
+                    out  [x]
+                    jt   1, loop
+            end:    hlt
+            x:      data 0
+            ",
+        )
+        .unwrap();
+        let mut computer = Intcode::new(program);
+        assert_eq!(computer.resume(), Ok(State::NeedsInput));
+        computer.push_input(21);
+        assert_eq!(computer.resume(), Ok(State::Output(42)));
+        assert_eq!(computer.resume(), Ok(State::NeedsInput));
+        computer.push_input(0);
+        assert_eq!(computer.resume(), Ok(State::Halted));
+        assert_eq!(computer.resume(), Ok(State::Halted));
+        assert!(computer.is_halted());
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        // accumulate inputs, outputting the running total
+        let program = asm::assemble(
+            "
+            loop:   in   [x]
+                    add  [x], [total], [total]
+                    out  [total]
+                    jt   1, loop
+            x:      data 0
+            total:  data 0
+            ",
+        )
+        .unwrap();
+        let mut computer = Intcode::new(program);
+        computer.push_input(5);
+        assert_eq!(computer.resume(), Ok(State::Output(5)));
+        let snapshot = computer.snapshot();
+
+        computer.push_input(1);
+        assert_eq!(computer.resume(), Ok(State::Output(6)));
+
+        // forking is independent of the original
+        let mut fork = computer.clone();
+        fork.push_input(100);
+        assert_eq!(fork.resume(), Ok(State::Output(106)));
+        computer.push_input(2);
+        assert_eq!(computer.resume(), Ok(State::Output(8)));
+
+        computer.restore(&snapshot);
+        computer.push_input(10);
+        assert_eq!(computer.resume(), Ok(State::Output(15)));
+
+        let mut restored = Intcode::from(snapshot);
+        restored.push_input(20);
+        assert_eq!(restored.resume(), Ok(State::Output(25)));
+    }
+
+    #[test]
+    fn test_clone_drops_channels() {
+        let (sender, receiver) = channel();
+        let computer = Intcode::new(vec![104, 1, 99]).with_outputs(sender);
+        let mut fork = computer.clone();
+        std::mem::drop(computer);
+        assert!(fork.run().is_err());
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_clone_keeps_configuration() {
+        let configured = |memory| {
+            Intcode::new(memory)
+                .with_arithmetic(Arithmetic::Checked)
+                .with_memory_limit(1024)
+                .with_budget(10)
+        };
+
+        let mut fork = configured(vec![1001, 5, 1, 5, 99, Word::MAX]).clone();
+        assert_eq!(fork.remaining_budget(), Some(10));
+        assert_eq!(
+            fork.resume(),
+            Err(IntcodeError::Overflow { ip: 0, word: 1001 })
+        );
+
+        let mut fork = configured(vec![1101, 1, 1, 5000, 99]).clone();
+        assert_eq!(
+            fork.resume(),
+            Err(IntcodeError::MemoryLimit {
+                ip: 0,
+                word: 1101,
+                address: 5000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_immediate_write() {
+        let mut computer = Intcode::new(vec![11_101, 1, 2, 3, 99]);
+        assert_eq!(
+            computer.run(),
+            Err(IntcodeError::ImmediateWrite {
+                ip: 0,
+                word: 11_101
+            })
+        );
+    }
+
+    #[test]
+    fn test_negative_relative_address() {
+        // arb -5; out [rb]
+        let mut computer = Intcode::new(vec![109, -5, 204, 0, 99]);
+        assert_eq!(
+            computer.run_collect(),
+            Err(IntcodeError::NegativeAddress {
+                ip: 2,
+                word: 204,
+                address: -5
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_words() {
+        let mut computer = Intcode::new(vec![1, 0, 0, 0, 42]);
+        assert_eq!(
+            computer.run(),
+            Err(IntcodeError::InvalidOpcode { ip: 4, word: 42 })
+        );
+        let mut computer = Intcode::new(vec![301, 0, 0, 0, 99]);
+        assert_eq!(
+            computer.run(),
+            Err(IntcodeError::InvalidMode { ip: 0, word: 301 })
+        );
+    }
+
+    #[test]
+    fn test_missing_streams() {
+        let mut computer = Intcode::new(vec![3, 0, 99]);
+        assert_eq!(
+            computer.run(),
+            Err(IntcodeError::InputStarved { ip: 0, word: 3 })
+        );
+        let mut computer = Intcode::new(vec![104, 7, 99]);
+        assert_eq!(
+            computer.run(),
+            Err(IntcodeError::OutputWithoutSink {
+                ip: 0,
+                word: 104,
+                value: 7
+            })
+        );
+    }
+
+    #[test]
+    fn test_budget() {
+        // jt 1, 0: loop forever
+        let mut computer = Intcode::new(vec![1105, 1, 0]).with_budget(100);
+        assert_eq!(computer.run(), Err(IntcodeError::BudgetExhausted { ip: 0 }));
+        assert_eq!(computer.retired(), 100);
+        assert_eq!(computer.remaining_budget(), Some(0));
+
+        // exhaustion is recoverable
+        computer.set_budget(Some(5));
+        assert_eq!(
+            computer.resume(),
+            Err(IntcodeError::BudgetExhausted { ip: 0 })
+        );
+        assert_eq!(computer.retired(), 105);
+
+        // the tighter of the two budgets applies
+        assert_eq!(
+            computer.run_for(10),
+            Err(IntcodeError::BudgetExhausted { ip: 0 })
+        );
+        assert_eq!(computer.retired(), 105);
+        computer.set_budget(None);
+        assert_eq!(
+            computer.run_for(10),
+            Err(IntcodeError::BudgetExhausted { ip: 0 })
+        );
+        assert_eq!(computer.retired(), 115);
+        assert_eq!(computer.remaining_budget(), None);
+    }
+
+    #[test]
+    fn test_run_for() {
+        let mut computer = Intcode::new(vec![104, 7, 99]);
+        assert_eq!(computer.run_for(1), Ok(State::Output(7)));
+        assert_eq!(
+            computer.run_for(0),
+            Err(IntcodeError::BudgetExhausted { ip: 2 })
+        );
+        assert_eq!(computer.run_for(1), Ok(State::Halted));
+        // halted machines stay halted, whatever the budget
+        assert_eq!(computer.run_for(0), Ok(State::Halted));
+        assert_eq!(computer.retired(), 2);
+    }
+
+    #[test]
+    fn test_paged_memory() {
+        // write far beyond the program, then read it back
+        let program = asm::assemble(
+            "
+                    arb  1000000000000
+                    add  [rb], 7, [rb]
+                    out  [rb]
+                    hlt
+            ",
+        )
+        .unwrap();
+        let mut computer = Intcode::with_memory(PagedMemory::from(program.clone()));
+        assert_eq!(computer.resume(), Ok(State::Output(7)));
+        assert_eq!(computer.memory().pages(), 2);
+
+        let mut computer = Intcode::new(program).with_memory_limit(1 << 20);
+        assert_eq!(
+            computer.resume(),
+            Err(IntcodeError::MemoryLimit {
+                ip: 2,
+                word: 21201,
+                address: 1_000_000_000_000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_memory_limit_keeps_input() {
+        let mut computer =
+            Intcode::with_memory(PagedMemory::from(vec![3, 5000, 99])).with_memory_limit(1024);
+        computer.push_input(1);
+        assert!(matches!(
+            computer.resume(),
+            Err(IntcodeError::MemoryLimit { address: 5000, .. })
+        ));
+        assert_eq!(computer.pending_inputs(), &[1]);
+
+        // zeros never need memory
+        computer.restore(&Snapshot {
+            pending_inputs: vec![0].into(),
+            ..computer.snapshot()
+        });
+        assert_eq!(computer.resume(), Ok(State::Halted));
+    }
+
+    #[test]
+    fn test_wrapping_arithmetic() {
+        let mut computer = Intcode::new(vec![1001, 5, 1, 5, 99, Word::MAX]);
+        computer.run().unwrap();
+        assert_eq!(computer.memory()[5], Word::MIN);
+
+        let mut computer =
+            Intcode::new(vec![1001, 5, 1, 5, 99, Word::MAX]).with_arithmetic(Arithmetic::Checked);
+        assert_eq!(
+            computer.run(),
+            Err(IntcodeError::Overflow { ip: 0, word: 1001 })
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Search every ordering of phase settings for a chain of amplifiers.
+//!
+//! Each amplifier runs a copy of the same program. It first receives its
+//! phase setting, then the signal from the previous amplifier (the first
+//! amplifier receives 0), and outputs a signal for the next. With feedback,
+//! the last amplifier's output returns to the first, and the signals go around
+//! until the amplifiers halt; the result is the last amplifier's final output.
+//!
+//! Orderings which share leading phases share the work of running those
+//! amplifiers: the search forks a snapshot of each prefix, rather than
+//! rerunning it for every ordering which begins with it. Distinct prefixes are
+//! explored in parallel.
+
+use super::{Intcode, IntcodeError, IntcodeMemory, Snapshot, State, Word};
+use rayon::prelude::*;
+use std::fmt;
+
+/// An ordering of phases and the signal it produces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    pub phases: Vec<Word>,
+    pub signal: Word,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmplifierError {
+    /// the last amplifier of this chain failed
+    Machine {
+        phases: Vec<Word>,
+        error: IntcodeError,
+    },
+    /// the last amplifier of this chain halted or wanted input without outputting a signal
+    NoSignal { phases: Vec<Word> },
+}
+
+impl fmt::Display for AmplifierError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AmplifierError::Machine { phases, error } => {
+                write!(f, "amplifier failed with phases {:?}: {}", phases, error)
+            }
+            AmplifierError::NoSignal { phases } => {
+                write!(f, "amplifier produced no signal with phases {:?}", phases)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AmplifierError {}
+
+/// run the last amplifier of `phases` until it outputs a signal
+fn next_signal(computer: &mut Intcode, phases: &[Word]) -> Result<Word, AmplifierError> {
+    match computer.resume() {
+        Ok(State::Output(signal)) => Ok(signal),
+        Ok(_) => Err(AmplifierError::NoSignal {
+            phases: phases.to_vec(),
+        }),
+        Err(error) => Err(AmplifierError::Machine {
+            phases: phases.to_vec(),
+            error,
+        }),
+    }
+}
+
+/// send the signal around the feedback loop until the amplifiers halt
+fn feed_back(
+    amplifiers: &[Snapshot],
+    phases: &[Word],
+    mut signal: Word,
+) -> Result<Word, AmplifierError> {
+    let mut amplifiers: Vec<Intcode> = amplifiers.iter().cloned().map(Intcode::from).collect();
+    loop {
+        for (idx, amplifier) in amplifiers.iter_mut().enumerate() {
+            amplifier.push_input(signal);
+            match amplifier.resume() {
+                Ok(State::Output(output)) => signal = output,
+                Ok(State::Halted) => return Ok(signal),
+                Ok(State::NeedsInput) => {
+                    return Err(AmplifierError::NoSignal {
+                        phases: phases[..=idx].to_vec(),
+                    })
+                }
+                Err(error) => {
+                    return Err(AmplifierError::Machine {
+                        phases: phases[..=idx].to_vec(),
+                        error,
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// rank every completion of the chain `prefix`, whose amplifiers are paused
+/// after emitting `signal`
+fn explore(
+    memory: &IntcodeMemory,
+    prefix: &[Word],
+    amplifiers: &[Snapshot],
+    signal: Word,
+    remaining: &[Word],
+    feedback: bool,
+) -> Result<Vec<Setting>, AmplifierError> {
+    if remaining.is_empty() {
+        let signal = if feedback {
+            feed_back(amplifiers, prefix, signal)?
+        } else {
+            signal
+        };
+        return Ok(vec![Setting {
+            phases: prefix.to_vec(),
+            signal,
+        }]);
+    }
+
+    let settings: Vec<Vec<Setting>> = remaining
+        .par_iter()
+        .map(|&phase| {
+            let mut phases = prefix.to_vec();
+            phases.push(phase);
+            let mut computer = Intcode::new(memory.clone());
+            computer.push_input(phase);
+            computer.push_input(signal);
+            let output = next_signal(&mut computer, &phases)?;
+
+            let mut amplifiers = amplifiers.to_vec();
+            if feedback {
+                amplifiers.push(computer.snapshot());
+            }
+            let remaining: Vec<Word> = remaining
+                .iter()
+                .cloned()
+                .filter(|&other| other != phase)
+                .collect();
+            explore(memory, &phases, &amplifiers, output, &remaining, feedback)
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(settings.into_iter().flatten().collect())
+}
+
+/// Evaluate every ordering of `phases`, best signal first.
+///
+/// Orderings with equal signals are sorted by their phases. `phases` should
+/// not contain duplicates.
+pub fn rank_phases(
+    memory: &IntcodeMemory,
+    phases: &[Word],
+    feedback: bool,
+) -> Result<Vec<Setting>, AmplifierError> {
+    let mut settings = explore(memory, &[], &[], 0, phases, feedback)?;
+    settings.sort_by(|a, b| {
+        b.signal
+            .cmp(&a.signal)
+            .then_with(|| a.phases.cmp(&b.phases))
+    });
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::topology::Topology;
+
+    fn feedback_example() -> IntcodeMemory {
+        vec![
+            3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26, 1001, 54,
+            -5, 54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55, 2, 53, 55, 53, 4,
+            53, 1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
+        ]
+    }
+
+    /// run one ordering the slow way, returning the last amplifier's final signal
+    fn compute_amplifier_stack(memory: &IntcodeMemory, phases: &[Word], feedback: bool) -> Word {
+        let name = |idx: usize| format!("amp{}", idx);
+        let mut topology = Topology::new();
+        for (idx, &phase) in phases.iter().enumerate() {
+            let inputs = if idx == 0 {
+                vec![phase, 0]
+            } else {
+                vec![phase]
+            };
+            topology = topology.with_machine(&name(idx), memory.clone(), &inputs);
+        }
+        for idx in 1..phases.len() {
+            topology = topology.with_edge(&name(idx - 1), &name(idx));
+        }
+        let last = name(phases.len() - 1);
+        if feedback {
+            topology = topology.with_edge(&last, &name(0));
+        }
+        topology.run().unwrap().output(&last).unwrap()
+    }
+
+    #[test]
+    fn test_chain() {
+        let memory = vec![
+            3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23, 1, 24, 23, 23, 4, 23,
+            99, 0, 0,
+        ];
+        let ranked = rank_phases(&memory, &[0, 1, 2, 3, 4], false).unwrap();
+        assert_eq!(ranked.len(), 120);
+        assert_eq!(
+            ranked[0],
+            Setting {
+                phases: vec![0, 1, 2, 3, 4],
+                signal: 54321,
+            }
+        );
+        for setting in &ranked {
+            assert_eq!(
+                setting.signal,
+                compute_amplifier_stack(&memory, &setting.phases, false)
+            );
+        }
+    }
+
+    #[test]
+    fn test_feedback() {
+        let memory = feedback_example();
+        let ranked = rank_phases(&memory, &[5, 6, 7, 8, 9], true).unwrap();
+        assert_eq!(ranked.len(), 120);
+        assert_eq!(
+            ranked[0],
+            Setting {
+                phases: vec![9, 7, 8, 5, 6],
+                signal: 18216,
+            }
+        );
+        assert!(ranked
+            .windows(2)
+            .all(|pair| pair[0].signal >= pair[1].signal));
+        for setting in ranked.iter().step_by(7) {
+            assert_eq!(
+                setting.signal,
+                compute_amplifier_stack(&memory, &setting.phases, true)
+            );
+        }
+    }
+
+    #[test]
+    fn test_no_signal() {
+        // reads its phase, then halts without a signal
+        let memory = vec![3, 0, 99];
+        assert!(matches!(
+            rank_phases(&memory, &[0, 1], false),
+            Err(AmplifierError::NoSignal { phases }) if phases.len() == 1
+        ));
+    }
+}
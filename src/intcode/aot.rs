@@ -0,0 +1,728 @@
+//! Translate Intcode programs into Rust, ahead of time.
+//!
+//! `translate` turns a program into the source of a module defining a type
+//! which implements `Translated`. Every reachable instruction is compiled into
+//! straight-line Rust, one `match` arm per basic block, so the program runs
+//! without decoding a single instruction. `Compiled` runs a translated program
+//! with the same input and output interface as `Intcode`.
+//!
+//! Translated code can't follow the program everywhere the interpreter can:
+//!
+//! - Code which is only reached through jumps to position- or relative-mode
+//!   targets isn't translated. When execution arrives there, it's interpreted
+//!   until it returns to the start of a translated block.
+//! - If the program writes to any cell of translated code, the translation no
+//!   longer describes the program, so the interpreter takes over for good.
+//!
+//! Translations use the standard instruction set, and their arithmetic wraps
+//! as `Arithmetic::Wrapping` does. Budgets and debugging hooks aren't
+//! available until the interpreter takes over.
+
+use super::{
+    channel,
+    disasm::{disassemble, render_at, Item},
+    isa::{Flow, InstructionSet, Mnemonic},
+    Intcode, IntcodeError, IntcodeMemory, Memory, Mode, Snapshot, State, Word,
+};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt::{self, Write};
+use std::marker::PhantomData;
+
+/// maximum quantity of words per line of the generated `PROGRAM`
+const WORDS_PER_LINE: usize = 12;
+
+/// The reason translated code returned control to `Compiled`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Output(Word),
+    NeedsInput,
+    Halted,
+    /// the ip isn't at the start of a translated block
+    Interpret,
+    /// the program wrote to translated code
+    Modified,
+}
+
+/// A program translated by `translate`.
+pub trait Translated {
+    /// the program as it was when it was translated
+    const PROGRAM: &'static [Word];
+    /// addresses at which translated blocks begin, ascending
+    const ENTRIES: &'static [usize];
+    /// the half-open ranges of cells occupied by translated code, ascending
+    const CODE: &'static [(usize, usize)];
+
+    /// run translated code from `state.ip` until it must return
+    fn execute(state: &mut Snapshot) -> Result<Exit, IntcodeError>;
+
+    fn is_entry(address: usize) -> bool {
+        Self::ENTRIES.binary_search(&address).is_ok()
+    }
+
+    fn is_code(address: usize) -> bool {
+        let idx = Self::CODE.partition_point(|&(start, _)| start <= address);
+        idx > 0 && address < Self::CODE[idx - 1].1
+    }
+}
+
+/// interpret `value` as an address, for translated code
+#[doc(hidden)]
+pub fn address(state: &Snapshot, ip: usize, value: Word) -> Result<usize, IntcodeError> {
+    if value < 0 {
+        return Err(IntcodeError::NegativeAddress {
+            ip,
+            word: state.memory.read(ip),
+            address: value,
+        });
+    }
+    Ok(value as usize)
+}
+
+/// resolve a relative-mode parameter, for translated code
+#[doc(hidden)]
+pub fn relative(state: &Snapshot, ip: usize, offset: Word) -> Result<usize, IntcodeError> {
+    let value = offset
+        .checked_add(state.relative_base_offset)
+        .ok_or(IntcodeError::Overflow {
+            ip,
+            word: state.memory.read(ip),
+        })?;
+    address(state, ip, value)
+}
+
+/// A translated program, running with the interface of an `Intcode` machine.
+pub struct Compiled<T> {
+    state: Snapshot,
+    /// set once the translation no longer describes the program
+    interpreter: Option<Intcode>,
+    inputs: Option<Receiver<Word>>,
+    outputs: Option<Sender<Word>>,
+    translated: PhantomData<T>,
+}
+
+impl<T: Translated> Default for Compiled<T> {
+    fn default() -> Self {
+        Compiled::new()
+    }
+}
+
+impl<T: Translated> Compiled<T> {
+    /// run the program as it was translated
+    pub fn new() -> Self {
+        Compiled {
+            state: Snapshot {
+                memory: T::PROGRAM.to_vec(),
+                ..Snapshot::default()
+            },
+            interpreter: None,
+            inputs: None,
+            outputs: None,
+            translated: PhantomData,
+        }
+    }
+
+    /// run the translated program from `memory`
+    ///
+    /// If `memory` differs from the translated program anywhere in its code,
+    /// for example because a solution patched it, it's interpreted instead.
+    pub fn with_memory(memory: IntcodeMemory) -> Self {
+        let mut compiled = Compiled::new();
+        compiled.state.memory = memory;
+        let unchanged = T::CODE.iter().all(|&(start, end)| {
+            (start..end).all(|address| compiled.state.memory.read(address) == T::PROGRAM[address])
+        });
+        if !unchanged {
+            compiled.fall_back();
+        }
+        compiled
+    }
+
+    pub fn with_inputs(mut self, inputs: Receiver<Word>) -> Self {
+        self.inputs = Some(inputs);
+        self
+    }
+
+    pub fn with_outputs(mut self, outputs: Sender<Word>) -> Self {
+        self.outputs = Some(outputs);
+        self
+    }
+
+    // convenience fn to initialize with static inputs
+    pub fn using_inputs(self, inputs: &[Word]) -> Self {
+        let (sender, receiver) = channel();
+        for &input in inputs {
+            sender.send(input).unwrap();
+        }
+        self.with_inputs(receiver)
+    }
+
+    /// queue an input for the program
+    ///
+    /// Queued inputs are consumed before any input stream.
+    pub fn push_input(&mut self, input: Word) {
+        match &mut self.interpreter {
+            Some(computer) => computer.push_input(input),
+            None => self.state.pending_inputs.push_back(input),
+        }
+    }
+
+    /// the address of the next instruction to execute
+    pub fn ip(&self) -> usize {
+        match &self.interpreter {
+            Some(computer) => computer.ip(),
+            None => self.state.ip,
+        }
+    }
+
+    pub fn memory(&self) -> &IntcodeMemory {
+        match &self.interpreter {
+            Some(computer) => computer.memory(),
+            None => &self.state.memory,
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        match &self.interpreter {
+            Some(computer) => computer.is_halted(),
+            None => self.state.halted,
+        }
+    }
+
+    /// whether the interpreter has taken over for good
+    pub fn is_interpreting(&self) -> bool {
+        self.interpreter.is_some()
+    }
+
+    /// run this program until it produces an output, needs an input, or halts
+    ///
+    /// Like `Intcode::resume`, this never blocks and never uses the streams.
+    pub fn resume(&mut self) -> Result<State, IntcodeError> {
+        loop {
+            if let Some(computer) = &mut self.interpreter {
+                return computer.resume();
+            }
+            if self.state.halted {
+                return Ok(State::Halted);
+            }
+            match T::execute(&mut self.state) {
+                Ok(Exit::Output(value)) => return Ok(State::Output(value)),
+                Ok(Exit::NeedsInput) => return Ok(State::NeedsInput),
+                Ok(Exit::Halted) => return Ok(State::Halted),
+                Ok(Exit::Modified) => self.fall_back(),
+                Ok(Exit::Interpret) => {
+                    if let Some(state) = self.interpret()? {
+                        return Ok(state);
+                    }
+                }
+                Err(err) => {
+                    self.state.ip = err.ip();
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    // run this program until completion, using the streams for input and output
+    pub fn run(&mut self) -> Result<(), IntcodeError> {
+        loop {
+            match self.resume()? {
+                State::Halted => return Ok(()),
+                State::Output(value) => {
+                    let sent = match &self.outputs {
+                        Some(outputs) => outputs.send(value).is_ok(),
+                        None => false,
+                    };
+                    if !sent {
+                        // an output instruction always occupies two cells
+                        let ip = self.ip() - 2;
+                        return Err(IntcodeError::OutputWithoutSink {
+                            ip,
+                            word: self.memory().read(ip),
+                            value,
+                        });
+                    }
+                }
+                State::NeedsInput => {
+                    let (ip, word) = (self.ip(), self.memory().read(self.ip()));
+                    let inputs = match &self.inputs {
+                        Some(inputs) => inputs,
+                        None => return Err(IntcodeError::InputStarved { ip, word }),
+                    };
+                    #[cfg(not(feature = "unbounded-input-time"))]
+                    let input_result = inputs.recv_timeout(std::time::Duration::new(1, 0));
+                    #[cfg(feature = "unbounded-input-time")]
+                    let input_result = inputs.recv().map_err(|_| RecvTimeoutError::Disconnected);
+                    match input_result {
+                        Ok(input) => self.push_input(input),
+                        Err(RecvTimeoutError::Timeout) => {
+                            return Err(IntcodeError::InputStarved { ip, word })
+                        }
+                        Err(RecvTimeoutError::Disconnected) => {
+                            return Err(IntcodeError::InputDisconnected { ip, word })
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // run this program into completion,
+    // collecting the outputs into a vector
+    pub fn run_collect(&mut self) -> Result<Vec<Word>, IntcodeError> {
+        let (sender, receiver) = channel();
+        self.outputs = Some(sender);
+        self.run()?;
+        std::mem::drop(self.outputs.take());
+        Ok(receiver.into_iter().collect())
+    }
+
+    /// hand the program to the interpreter for good
+    fn fall_back(&mut self) {
+        let state = std::mem::take(&mut self.state);
+        self.interpreter = Some(Intcode::from(state));
+    }
+
+    /// interpret from the current ip until translated code can take over again
+    ///
+    /// Returns the state, if the interpreter had to return to the caller first.
+    fn interpret(&mut self) -> Result<Option<State>, IntcodeError> {
+        let mut computer = Intcode::from(std::mem::take(&mut self.state));
+        let result = loop {
+            if T::is_entry(computer.ip) {
+                break Ok(None);
+            }
+            let write = computer.accesses().ok().and_then(|accesses| accesses.write);
+            if write.is_some_and(T::is_code) {
+                self.interpreter = Some(computer);
+                return Ok(None);
+            }
+            match computer.step() {
+                Ok(None) => continue,
+                Ok(Some(state)) => break Ok(Some(state)),
+                Err(err) => break Err(err),
+            }
+        };
+        self.state = Snapshot {
+            ip: computer.ip,
+            memory: computer.memory,
+            halted: computer.halted,
+            relative_base_offset: computer.relative_base_offset,
+            pending_inputs: computer.pending_inputs,
+        };
+        result
+    }
+}
+
+/// a reachable instruction, as the translator sees it
+struct Instruction {
+    address: usize,
+    mnemonic: Mnemonic,
+    modes: Vec<Mode>,
+    operands: Vec<Word>,
+}
+
+impl Instruction {
+    fn next(&self) -> usize {
+        self.address + 1 + self.mnemonic.arity
+    }
+
+    /// whether control may leave this instruction other than by falling through
+    fn ends_block(&self) -> bool {
+        self.mnemonic.flow != Flow::Next || self.mnemonic.opcode == 4
+    }
+}
+
+/// Translate `memory` into the source of a Rust module defining `name`, a
+/// type implementing `Translated`.
+///
+/// `intcode_path` is the path by which the generated code refers to this
+/// module: `aoc2019::intcode` from other crates, or `crate::intcode` within
+/// this one. The generated module should be included once per translation.
+pub fn translate(memory: &IntcodeMemory, name: &str, intcode_path: &str) -> String {
+    let mut source = String::new();
+    write_translation(&mut source, memory, name, intcode_path)
+        .expect("writing to a String can't fail");
+    source
+}
+
+fn write_translation(
+    out: &mut String,
+    memory: &IntcodeMemory,
+    name: &str,
+    intcode_path: &str,
+) -> fmt::Result {
+    let instructions: Vec<Instruction> = disassemble(memory)
+        .items
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::Instruction {
+                address,
+                mnemonic,
+                modes,
+                operands,
+            } => Some(Instruction {
+                address,
+                mnemonic,
+                modes,
+                operands,
+            }),
+            Item::Data { .. } => None,
+        })
+        .collect();
+    let blocks = blocks(&instructions);
+    let code = code_ranges(&instructions);
+    let is_code = |address: usize| {
+        code.iter()
+            .any(|&(start, end)| (start..end).contains(&address))
+    };
+
+    writeln!(out, "// Translated from Intcode; do not edit.")?;
+    writeln!(out)?;
+    writeln!(out, "#[allow(unused_imports)]")?;
+    writeln!(
+        out,
+        "use {}::{{aot::{{self, Exit, Translated}}, IntcodeError, Memory, Snapshot, Word}};",
+        intcode_path
+    )?;
+    writeln!(out)?;
+    writeln!(out, "pub struct {};", name)?;
+    writeln!(out)?;
+    writeln!(out, "impl Translated for {} {{", name)?;
+    writeln!(out, "    const PROGRAM: &'static [Word] = &[")?;
+    for line in memory.chunks(WORDS_PER_LINE) {
+        let words: Vec<String> = line.iter().map(Word::to_string).collect();
+        writeln!(out, "        {},", words.join(", "))?;
+    }
+    writeln!(out, "    ];")?;
+    let entries: Vec<String> = blocks
+        .iter()
+        .map(|block| block[0].address.to_string())
+        .collect();
+    writeln!(
+        out,
+        "    const ENTRIES: &'static [usize] = &[{}];",
+        entries.join(", ")
+    )?;
+    let ranges: Vec<String> = code
+        .iter()
+        .map(|(start, end)| format!("({}, {})", start, end))
+        .collect();
+    writeln!(
+        out,
+        "    const CODE: &'static [(usize, usize)] = &[{}];",
+        ranges.join(", ")
+    )?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "    fn execute(s: &mut Snapshot) -> Result<Exit, IntcodeError> {{"
+    )?;
+    writeln!(out, "        loop {{")?;
+    writeln!(out, "            match s.ip {{")?;
+    let isa = InstructionSet::<Word>::standard();
+    for block in blocks {
+        writeln!(out, "                {} => {{", block[0].address)?;
+        for instruction in block {
+            let rendered = render_at(memory, instruction.address, &isa).unwrap_or_default();
+            writeln!(
+                out,
+                "                    // {}: {}",
+                instruction.address, rendered
+            )?;
+            write_instruction(out, instruction, &is_code)?;
+        }
+        let last = block.last().expect("blocks are never empty");
+        if !last.ends_block() {
+            writeln!(out, "                    s.ip = {};", last.next())?;
+        }
+        writeln!(out, "                }}")?;
+    }
+    writeln!(out, "                _ => return Ok(Exit::Interpret),")?;
+    writeln!(out, "            }}")?;
+    writeln!(out, "        }}")?;
+    writeln!(out, "    }}")?;
+    writeln!(out, "}}")
+}
+
+/// split reachable instructions into basic blocks
+///
+/// Blocks begin wherever translated code may be entered: at static jump
+/// targets, after jumps, at inputs so they can wait for a value, after
+/// outputs so they can resume, and wherever reachable code isn't contiguous.
+fn blocks(instructions: &[Instruction]) -> Vec<&[Instruction]> {
+    let addresses: BTreeMap<usize, usize> = instructions
+        .iter()
+        .enumerate()
+        .map(|(idx, instruction)| (instruction.address, idx))
+        .collect();
+    let mut leaders = BTreeSet::new();
+    for (idx, instruction) in instructions.iter().enumerate() {
+        let contiguous = idx > 0 && instructions[idx - 1].next() == instruction.address;
+        if !contiguous || instruction.mnemonic.opcode == 3 {
+            leaders.insert(idx);
+        }
+        if instruction.ends_block() {
+            leaders.insert(idx + 1);
+        }
+        let static_target = match instruction.mnemonic.flow {
+            Flow::JumpIf(_) if instruction.modes[1] == Mode::Immediate => {
+                usize::try_from(instruction.operands[1]).ok()
+            }
+            _ => None,
+        };
+        if let Some(&target) = static_target.and_then(|target| addresses.get(&target)) {
+            leaders.insert(target);
+        }
+    }
+    leaders.insert(instructions.len());
+    leaders
+        .iter()
+        .zip(leaders.iter().skip(1))
+        .map(|(&start, &end)| &instructions[start..end])
+        .collect()
+}
+
+/// the half-open ranges of cells which reachable instructions occupy
+fn code_ranges(instructions: &[Instruction]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for instruction in instructions {
+        match ranges.last_mut() {
+            Some((_, end)) if *end == instruction.address => *end = instruction.next(),
+            _ => ranges.push((instruction.address, instruction.next())),
+        }
+    }
+    ranges
+}
+
+/// an expression for the value of the read parameter `idx`
+fn operand(instruction: &Instruction, idx: usize) -> String {
+    let (ip, value) = (instruction.address, instruction.operands[idx]);
+    match instruction.modes[idx] {
+        Mode::Immediate => value.to_string(),
+        Mode::Position if value >= 0 => format!("s.memory.read({})", value),
+        Mode::Position => format!("s.memory.read(aot::address(s, {}, {})?)", ip, value),
+        Mode::Relative => format!("s.memory.read(aot::relative(s, {}, {})?)", ip, value),
+    }
+}
+
+/// an expression for the address written by the parameter `idx`, and whether
+/// it's statically known not to be translated code
+fn destination(
+    instruction: &Instruction,
+    idx: usize,
+    is_code: impl Fn(usize) -> bool,
+) -> (String, bool) {
+    let (ip, value) = (instruction.address, instruction.operands[idx]);
+    match instruction.modes[idx] {
+        Mode::Position if value >= 0 => (value.to_string(), !is_code(value as usize)),
+        Mode::Position => (format!("aot::address(s, {}, {})?", ip, value), true),
+        _ => (format!("aot::relative(s, {}, {})?", ip, value), false),
+    }
+}
+
+fn write_instruction(
+    out: &mut String,
+    instruction: &Instruction,
+    is_code: &impl Fn(usize) -> bool,
+) -> fmt::Result {
+    const INDENT: &str = "                    ";
+    let (ip, next) = (instruction.address, instruction.next());
+    let store = |out: &mut String, value: &str| -> fmt::Result {
+        let (address, not_code) = destination(instruction, 2, is_code);
+        writeln!(out, "{}let address = {};", INDENT, address)?;
+        writeln!(out, "{}s.memory.write(address, {});", INDENT, value)?;
+        write_modification_check(out, not_code, next)
+    };
+    match instruction.mnemonic.opcode {
+        1 | 2 | 7 | 8 => {
+            writeln!(out, "{}let a: Word = {};", INDENT, operand(instruction, 0))?;
+            writeln!(out, "{}let b: Word = {};", INDENT, operand(instruction, 1))?;
+            let value = match instruction.mnemonic.opcode {
+                1 => "a.wrapping_add(b)",
+                2 => "a.wrapping_mul(b)",
+                7 => "(a < b) as Word",
+                _ => "(a == b) as Word",
+            };
+            store(out, value)?;
+        }
+        3 => {
+            let (address, not_code) = destination(instruction, 0, is_code);
+            writeln!(out, "{}let address = {};", INDENT, address)?;
+            writeln!(
+                out,
+                "{}let input = match s.pending_inputs.pop_front() {{",
+                INDENT
+            )?;
+            writeln!(out, "{}    Some(input) => input,", INDENT)?;
+            writeln!(out, "{}    None => return Ok(Exit::NeedsInput),", INDENT)?;
+            writeln!(out, "{}}};", INDENT)?;
+            writeln!(out, "{}s.memory.write(address, input);", INDENT)?;
+            write_modification_check(out, not_code, next)?;
+        }
+        4 => {
+            writeln!(out, "{}let a: Word = {};", INDENT, operand(instruction, 0))?;
+            writeln!(out, "{}s.ip = {};", INDENT, next)?;
+            writeln!(out, "{}return Ok(Exit::Output(a));", INDENT)?;
+        }
+        5 | 6 => {
+            let jumps_when = instruction.mnemonic.opcode == 5;
+            let target = match instruction.modes[1] {
+                Mode::Immediate if instruction.operands[1] >= 0 => {
+                    instruction.operands[1].to_string()
+                }
+                Mode::Immediate => format!("aot::address(s, {}, {})?", ip, instruction.operands[1]),
+                _ => format!("aot::address(s, {}, {})?", ip, operand(instruction, 1)),
+            };
+            match instruction.modes[0] {
+                Mode::Immediate if (instruction.operands[0] != 0) == jumps_when => {
+                    writeln!(out, "{}s.ip = {};", INDENT, target)?;
+                    writeln!(out, "{}continue;", INDENT)?;
+                }
+                Mode::Immediate => writeln!(out, "{}s.ip = {};", INDENT, next)?,
+                _ => {
+                    let comparison = if jumps_when { "!=" } else { "==" };
+                    writeln!(
+                        out,
+                        "{}if {} {} 0 {{",
+                        INDENT,
+                        operand(instruction, 0),
+                        comparison
+                    )?;
+                    writeln!(out, "{}    s.ip = {};", INDENT, target)?;
+                    writeln!(out, "{}    continue;", INDENT)?;
+                    writeln!(out, "{}}}", INDENT)?;
+                    writeln!(out, "{}s.ip = {};", INDENT, next)?;
+                }
+            }
+        }
+        9 => {
+            writeln!(out, "{}let a: Word = {};", INDENT, operand(instruction, 0))?;
+            writeln!(
+                out,
+                "{}s.relative_base_offset = s.relative_base_offset.wrapping_add(a);",
+                INDENT
+            )?;
+        }
+        99 => {
+            writeln!(out, "{}s.ip = {};", INDENT, ip)?;
+            writeln!(out, "{}s.halted = true;", INDENT)?;
+            writeln!(out, "{}return Ok(Exit::Halted);", INDENT)?;
+        }
+        opcode => unreachable!("opcode {} isn't in the standard set", opcode),
+    }
+    Ok(())
+}
+
+/// hand over to the interpreter if a write might have modified translated code
+fn write_modification_check(out: &mut String, not_code: bool, next: usize) -> fmt::Result {
+    const INDENT: &str = "                    ";
+    if not_code {
+        return Ok(());
+    }
+    writeln!(out, "{}if Self::is_code(address) {{", INDENT)?;
+    writeln!(out, "{}    s.ip = {};", INDENT, next)?;
+    writeln!(out, "{}    return Ok(Exit::Modified);", INDENT)?;
+    writeln!(out, "{}}}", INDENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    mod quine {
+        include!("aot/quine.rs");
+    }
+
+    mod hidden {
+        include!("aot/hidden.rs");
+    }
+
+    use hidden::Hidden;
+    use quine::Quine;
+
+    /// sum inputs until a zero, via a dynamic jump to code which isn't
+    /// translated, then overwrite the final output with a halt
+    const HIDDEN: &str = "
+            loop:   in   [x]
+                    jf   [x], done
+                    add  [x], [total], [total]
+                    out  [total]
+                    jt   1, [back]
+            hidden: out  -1
+                    jt   1, loop
+            done:   add  99, 0, [patch]
+            patch:  out  [total]
+                    hlt
+            back:   data hidden
+            x:      data 0
+            total:  data 0
+            ";
+
+    #[test]
+    fn test_translations_are_current() {
+        let hidden = assemble(HIDDEN).unwrap();
+        assert_eq!(hidden, Hidden::PROGRAM);
+        assert_eq!(
+            translate(&hidden, "Hidden", "crate::intcode"),
+            include_str!("aot/hidden.rs")
+        );
+        assert_eq!(
+            translate(&Quine::PROGRAM.to_vec(), "Quine", "crate::intcode"),
+            include_str!("aot/quine.rs")
+        );
+    }
+
+    #[test]
+    fn test_quine() {
+        let mut compiled = Compiled::<Quine>::new();
+        assert_eq!(compiled.run_collect().unwrap(), Quine::PROGRAM);
+        assert!(!compiled.is_interpreting());
+    }
+
+    #[test]
+    fn test_matches_interpreter() {
+        let mut compiled = Compiled::<Hidden>::new();
+        let mut computer = Intcode::new(Hidden::PROGRAM.to_vec());
+        assert_eq!(compiled.resume(), Ok(State::NeedsInput));
+        assert_eq!(computer.resume(), Ok(State::NeedsInput));
+        for &input in &[1, 2, 0] {
+            compiled.push_input(input);
+            computer.push_input(input);
+            loop {
+                let state = compiled.resume();
+                assert_eq!(state, computer.resume());
+                match state {
+                    // untranslated code hands back to translated code
+                    Ok(State::Output(_)) => assert!(!compiled.is_interpreting()),
+                    _ => break,
+                }
+            }
+        }
+        // the final output was patched out
+        assert!(compiled.is_interpreting());
+        assert!(compiled.is_halted());
+        assert_eq!(compiled.memory(), computer.memory());
+    }
+
+    #[test]
+    fn test_streams_and_patched_memory() {
+        let mut compiled = Compiled::<Hidden>::new().using_inputs(&[3, 4, 0]);
+        assert_eq!(compiled.run_collect(), Ok(vec![3, -1, 7, -1]));
+
+        // patching code means the translation doesn't apply
+        let mut memory = Hidden::PROGRAM.to_vec();
+        memory[10] = 27;
+        let mut compiled = Compiled::<Hidden>::with_memory(memory).using_inputs(&[5, 0]);
+        assert!(compiled.is_interpreting());
+        assert_eq!(compiled.run_collect(), Ok(vec![5, -1]));
+
+        let mut compiled = Compiled::<Hidden>::new();
+        assert_eq!(
+            compiled.run(),
+            Err(IntcodeError::InputStarved { ip: 0, word: 3 })
+        );
+    }
+}
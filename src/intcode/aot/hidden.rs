@@ -0,0 +1,76 @@
+// Translated from Intcode; do not edit.
+
+#[allow(unused_imports)]
+use crate::intcode::{aot::{self, Exit, Translated}, IntcodeError, Memory, Snapshot, Word};
+
+pub struct Hidden;
+
+impl Translated for Hidden {
+    const PROGRAM: &'static [Word] = &[
+        3, 27, 1006, 27, 19, 1, 27, 28, 28, 4, 28, 105,
+        1, 26, 104, -1, 1105, 1, 0, 1101, 99, 0, 23, 4,
+        28, 99, 14, 0, 0,
+    ];
+    const ENTRIES: &'static [usize] = &[0, 5, 11, 19, 25];
+    const CODE: &'static [(usize, usize)] = &[(0, 14), (19, 26)];
+
+    fn execute(s: &mut Snapshot) -> Result<Exit, IntcodeError> {
+        loop {
+            match s.ip {
+                0 => {
+                    // 0: in   [27]
+                    let address = 27;
+                    let input = match s.pending_inputs.pop_front() {
+                        Some(input) => input,
+                        None => return Ok(Exit::NeedsInput),
+                    };
+                    s.memory.write(address, input);
+                    // 2: jf   [27], 19
+                    if s.memory.read(27) == 0 {
+                        s.ip = 19;
+                        continue;
+                    }
+                    s.ip = 5;
+                }
+                5 => {
+                    // 5: add  [27], [28], [28]
+                    let a: Word = s.memory.read(27);
+                    let b: Word = s.memory.read(28);
+                    let address = 28;
+                    s.memory.write(address, a.wrapping_add(b));
+                    // 9: out  [28]
+                    let a: Word = s.memory.read(28);
+                    s.ip = 11;
+                    return Ok(Exit::Output(a));
+                }
+                11 => {
+                    // 11: jt   1, [26]
+                    s.ip = aot::address(s, 11, s.memory.read(26))?;
+                    continue;
+                }
+                19 => {
+                    // 19: add  99, 0, [23]
+                    let a: Word = 99;
+                    let b: Word = 0;
+                    let address = 23;
+                    s.memory.write(address, a.wrapping_add(b));
+                    if Self::is_code(address) {
+                        s.ip = 23;
+                        return Ok(Exit::Modified);
+                    }
+                    // 23: out  [28]
+                    let a: Word = s.memory.read(28);
+                    s.ip = 25;
+                    return Ok(Exit::Output(a));
+                }
+                25 => {
+                    // 25: hlt
+                    s.ip = 25;
+                    s.halted = true;
+                    return Ok(Exit::Halted);
+                }
+                _ => return Ok(Exit::Interpret),
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+// Translated from Intcode; do not edit.
+
+#[allow(unused_imports)]
+use crate::intcode::{aot::{self, Exit, Translated}, IntcodeError, Memory, Snapshot, Word};
+
+pub struct Quine;
+
+impl Translated for Quine {
+    const PROGRAM: &'static [Word] = &[
+        109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101,
+        1006, 101, 0, 99,
+    ];
+    const ENTRIES: &'static [usize] = &[0, 4, 15];
+    const CODE: &'static [(usize, usize)] = &[(0, 16)];
+
+    fn execute(s: &mut Snapshot) -> Result<Exit, IntcodeError> {
+        loop {
+            match s.ip {
+                0 => {
+                    // 0: arb  1
+                    let a: Word = 1;
+                    s.relative_base_offset = s.relative_base_offset.wrapping_add(a);
+                    // 2: out  [rb-1]
+                    let a: Word = s.memory.read(aot::relative(s, 2, -1)?);
+                    s.ip = 4;
+                    return Ok(Exit::Output(a));
+                }
+                4 => {
+                    // 4: add  [100], 1, [100]
+                    let a: Word = s.memory.read(100);
+                    let b: Word = 1;
+                    let address = 100;
+                    s.memory.write(address, a.wrapping_add(b));
+                    // 8: eq   [100], 16, [101]
+                    let a: Word = s.memory.read(100);
+                    let b: Word = 16;
+                    let address = 101;
+                    s.memory.write(address, (a == b) as Word);
+                    // 12: jf   [101], 0
+                    if s.memory.read(101) == 0 {
+                        s.ip = 0;
+                        continue;
+                    }
+                    s.ip = 15;
+                }
+                15 => {
+                    // 15: hlt
+                    s.ip = 15;
+                    s.halted = true;
+                    return Ok(Exit::Halted);
+                }
+                _ => return Ok(Exit::Interpret),
+            }
+        }
+    }
+}
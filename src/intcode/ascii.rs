@@ -0,0 +1,220 @@
+//! Talk to Intcode programs which speak ASCII.
+//!
+//! Many programs read commands as lines of ASCII text and print their
+//! responses the same way, then report a final answer as a single word too
+//! large to be a character. `AsciiComputer` turns that protocol into lines of
+//! text in both directions, with the out-of-range words reported separately.
+
+use super::{Intcode, IntcodeError, IntcodeMemory, Memory, State, Word};
+use std::collections::VecDeque;
+use std::fmt;
+
+/// largest word which is treated as a character
+pub const MAX_ASCII: Word = 127;
+
+const NEWLINE: Word = b'\n' as Word;
+
+/// What an ASCII program did when resumed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// a line of text, without its newline
+    ///
+    /// A partial line is also emitted when the program asks for input or
+    /// halts, as prompts often lack a trailing newline.
+    Line(String),
+    /// an output word outside the ASCII range
+    Value(Word),
+    NeedsInput,
+    Halted,
+}
+
+/// Everything a program output before it next needed input or halted.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Transcript {
+    pub lines: Vec<String>,
+    pub values: Vec<Word>,
+}
+
+impl Transcript {
+    /// the lines, joined by newlines
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+}
+
+/// A line of input contained a character outside ASCII.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotAscii(pub char);
+
+impl fmt::Display for NotAscii {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "not an ASCII character: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for NotAscii {}
+
+fn check_ascii(line: &str) -> Result<(), NotAscii> {
+    match line.chars().find(|c| !c.is_ascii()) {
+        Some(c) => Err(NotAscii(c)),
+        None => Ok(()),
+    }
+}
+
+/// An Intcode machine which reads and writes lines of ASCII text.
+#[derive(Debug, Clone)]
+pub struct AsciiComputer<M: Memory = IntcodeMemory> {
+    computer: Intcode<M>,
+    line: String,
+    pending: VecDeque<Event>,
+}
+
+impl<M: Memory<Word = Word>> AsciiComputer<M> {
+    pub fn new(computer: Intcode<M>) -> Self {
+        AsciiComputer {
+            computer,
+            line: String::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// queue a line of input, followed by a newline
+    ///
+    /// Nothing is queued if the line isn't all ASCII.
+    pub fn send_line(&mut self, line: &str) -> Result<(), NotAscii> {
+        check_ascii(line)?;
+        for c in line.chars() {
+            self.computer.push_input(c as Word);
+        }
+        self.computer.push_input(NEWLINE);
+        Ok(())
+    }
+
+    /// queue several lines of input
+    ///
+    /// Nothing is queued if any line isn't all ASCII.
+    pub fn send_lines<'a>(
+        &mut self,
+        lines: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), NotAscii> {
+        let lines: Vec<&str> = lines.into_iter().collect();
+        for line in &lines {
+            check_ascii(line)?;
+        }
+        for line in lines {
+            self.send_line(line)?;
+        }
+        Ok(())
+    }
+
+    /// run until the program completes a line, outputs a value, needs input or halts
+    pub fn resume(&mut self) -> Result<Event, IntcodeError> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(event);
+        }
+        loop {
+            let event = match self.computer.resume()? {
+                State::Output(NEWLINE) => return Ok(Event::Line(std::mem::take(&mut self.line))),
+                State::Output(value) if (0..=MAX_ASCII).contains(&value) => {
+                    self.line.push(value as u8 as char);
+                    continue;
+                }
+                State::Output(value) => Event::Value(value),
+                State::NeedsInput => Event::NeedsInput,
+                State::Halted => Event::Halted,
+            };
+            if self.line.is_empty() {
+                return Ok(event);
+            }
+            self.pending.push_back(event);
+            return Ok(Event::Line(std::mem::take(&mut self.line)));
+        }
+    }
+
+    /// run until the program needs input or halts, collecting its output
+    pub fn run(&mut self) -> Result<Transcript, IntcodeError> {
+        let mut transcript = Transcript::default();
+        loop {
+            match self.resume()? {
+                Event::Line(line) => transcript.lines.push(line),
+                Event::Value(value) => transcript.values.push(value),
+                Event::NeedsInput | Event::Halted => return Ok(transcript),
+            }
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.pending.is_empty() && self.computer.is_halted()
+    }
+
+    pub fn computer(&self) -> &Intcode<M> {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Intcode<M> {
+        &mut self.computer
+    }
+
+    pub fn into_inner(self) -> Intcode<M> {
+        self.computer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    /// print a prompt, echo one line of input, then output 1000
+    fn echo() -> AsciiComputer {
+        let memory = assemble(
+            r#"
+                    arb  prompt
+            print:  out  [rb]
+                    arb  1
+                    jt   [rb], print
+            echo:   in   [c]
+                    eq   [c], 10, [done]
+                    jt   [done], end
+                    out  [c]
+                    jt   1, echo
+            end:    out  1000
+                    hlt
+            c:      data 0
+            done:   data 0
+            prompt: data "Name?", 0
+            "#,
+        )
+        .unwrap();
+        AsciiComputer::new(Intcode::new(memory))
+    }
+
+    #[test]
+    fn test_conversation() {
+        let mut computer = echo();
+        assert_eq!(computer.resume(), Ok(Event::Line("Name?".into())));
+        assert_eq!(computer.resume(), Ok(Event::NeedsInput));
+        assert_eq!(computer.send_line("Adà"), Err(NotAscii('à')));
+        assert!(computer.computer().pending_inputs().is_empty());
+        computer.send_line("Ada").unwrap();
+        assert_eq!(computer.resume(), Ok(Event::Line("Ada".into())));
+        assert_eq!(computer.resume(), Ok(Event::Value(1000)));
+        assert_eq!(computer.resume(), Ok(Event::Halted));
+        assert!(computer.is_halted());
+    }
+
+    #[test]
+    fn test_run() {
+        let mut computer = echo();
+        assert_eq!(
+            computer.send_lines(vec!["Grace", "ignorée"]),
+            Err(NotAscii('é'))
+        );
+        assert!(computer.computer().pending_inputs().is_empty());
+        computer.send_lines(vec!["Grace", "ignored"]).unwrap();
+        let transcript = computer.run().unwrap();
+        assert_eq!(transcript.text(), "Name?Grace");
+        assert_eq!(transcript.values, vec![1000]);
+        assert!(computer.is_halted());
+    }
+}
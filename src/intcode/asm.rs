@@ -0,0 +1,744 @@
+//! A small assembly language for Intcode.
+//!
+//! Each line holds an optional label, an optional statement, and an optional
+//! comment:
+//!
+//! ```text
+//! ; echo inputs until we see a zero
+//! loop:   in   [value]
+//!         jf   [value], done
+//!         out  [value]
+//!         jt   1, loop
+//! done:   hlt
+//! value:  data 0
+//! ```
+//!
+//! Operands are written according to their mode:
+//!
+//! - immediate: `5`, `-3`, `loop`, `loop+2`
+//! - position: `[5]`, `[value]`
+//! - relative: `[rb]`, `[rb+3]`, `[rb-1]`
+//!
+//! The `data` directive emits its comma-separated values verbatim; string
+//! literals emit one word per byte. The `space` directive emits the given
+//! quantity of zeroes, up to `MAX_SPACE`.
+
+use super::{
+    isa::{InstructionSet, Mnemonic},
+    IntcodeMemory, Mode, Word,
+};
+use std::collections::HashMap;
+use std::fmt;
+
+/// The digit which encodes a mode within an instruction word
+pub fn mode_digit(mode: Mode) -> Word {
+    match mode {
+        Mode::Position => 0,
+        Mode::Immediate => 1,
+        Mode::Relative => 2,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    UnexpectedChar(char),
+    UnexpectedEnd,
+    InvalidNumber(String),
+    UnterminatedString,
+    UnknownMnemonic(String),
+    WrongOperandCount {
+        mnemonic: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    ImmediateWrite(&'static str),
+    DuplicateLabel(String),
+    UndefinedLabel(String),
+    ReservedName(String),
+    NegativeSpace(Word),
+    SpaceTooLarge(Word),
+    /// the value of an expression doesn't fit in a word
+    Overflow,
+}
+
+impl fmt::Display for AsmErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use AsmErrorKind::*;
+        match self {
+            UnexpectedChar(c) => write!(f, "unexpected character {:?}", c),
+            UnexpectedEnd => write!(f, "unexpected end of line"),
+            InvalidNumber(n) => write!(f, "invalid number: {}", n),
+            UnterminatedString => write!(f, "unterminated string literal"),
+            UnknownMnemonic(m) => write!(f, "unknown mnemonic: {}", m),
+            WrongOperandCount {
+                mnemonic,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} takes {} operands but {} were given",
+                mnemonic, expected, found
+            ),
+            ImmediateWrite(m) => write!(f, "{} cannot write to an immediate operand", m),
+            DuplicateLabel(l) => write!(f, "label defined more than once: {}", l),
+            UndefinedLabel(l) => write!(f, "undefined label: {}", l),
+            ReservedName(l) => write!(f, "reserved name cannot be a label: {}", l),
+            NegativeSpace(n) => write!(f, "cannot reserve negative space: {}", n),
+            SpaceTooLarge(n) => write!(
+                f,
+                "cannot reserve more than {} words of space: {}",
+                MAX_SPACE, n
+            ),
+            Overflow => write!(f, "expression overflows"),
+        }
+    }
+}
+
+/// An assembly error, located by its 1-based line and column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: AsmErrorKind,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// Assemble a program into memory which `Intcode::new` can run directly.
+pub fn assemble(source: &str) -> Result<IntcodeMemory, AsmError> {
+    assemble_with(source, &InstructionSet::<Word>::standard())
+}
+
+/// Assemble a program written for the instructions of `isa`.
+pub fn assemble_with<W>(source: &str, isa: &InstructionSet<W>) -> Result<IntcodeMemory, AsmError> {
+    let mut statements = Vec::new();
+    let mut labels = HashMap::new();
+    let mut address: usize = 0;
+
+    // first pass: parse everything and learn where the labels point
+    for (idx, text) in source.lines().enumerate() {
+        let mut line = Line::parse(idx + 1, text, isa)?;
+        for (label, column) in std::mem::take(&mut line.labels) {
+            if label == REGISTER {
+                return Err(line.error(column, AsmErrorKind::ReservedName(label)));
+            }
+            if labels.insert(label.clone(), address).is_some() {
+                return Err(line.error(column, AsmErrorKind::DuplicateLabel(label)));
+            }
+        }
+        if let Some(statement) = line.statement.take() {
+            address += statement.size(&line)?;
+            statements.push((line.number, statement));
+        }
+    }
+
+    // second pass: emit
+    let mut memory = IntcodeMemory::with_capacity(address);
+    for (number, statement) in statements {
+        statement.emit(number, &labels, &mut memory)?;
+    }
+    Ok(memory)
+}
+
+/// the name of the relative base register in operand syntax
+pub const REGISTER: &str = "rb";
+
+/// most words a single `space` directive may reserve
+pub const MAX_SPACE: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sign {
+    Plus,
+    Minus,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Number(Word),
+    Label(String),
+}
+
+/// a sum of terms, each of which is located by its column
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Expr(Vec<(Sign, Term, usize)>);
+
+impl Expr {
+    fn zero() -> Expr {
+        Expr(Vec::new())
+    }
+
+    fn evaluate(&self, line: usize, labels: &HashMap<String, usize>) -> Result<Word, AsmError> {
+        let mut value: Word = 0;
+        for (sign, term, column) in &self.0 {
+            let term = match term {
+                Term::Number(n) => *n,
+                Term::Label(label) => *labels.get(label).ok_or_else(|| AsmError {
+                    line,
+                    column: *column,
+                    kind: AsmErrorKind::UndefinedLabel(label.clone()),
+                })? as Word,
+            };
+            let sum = match sign {
+                Sign::Plus => value.checked_add(term),
+                Sign::Minus => value.checked_sub(term),
+            };
+            value = sum.ok_or(AsmError {
+                line,
+                column: *column,
+                kind: AsmErrorKind::Overflow,
+            })?;
+        }
+        Ok(value)
+    }
+
+    /// evaluate an expression which may only contain literals
+    fn constant(&self, line: usize) -> Result<Word, AsmError> {
+        self.evaluate(line, &HashMap::new())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Operand {
+    mode: Mode,
+    expr: Expr,
+    column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DataItem {
+    Expr(Expr),
+    Str(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Statement {
+    Instruction {
+        mnemonic: Mnemonic,
+        operands: Vec<Operand>,
+    },
+    Data(Vec<DataItem>),
+    Space(Expr, usize),
+}
+
+impl Statement {
+    fn size(&self, line: &Line) -> Result<usize, AsmError> {
+        Ok(match self {
+            Statement::Instruction { mnemonic, .. } => 1 + mnemonic.arity,
+            Statement::Data(items) => items
+                .iter()
+                .map(|item| match item {
+                    DataItem::Expr(_) => 1,
+                    DataItem::Str(bytes) => bytes.len(),
+                })
+                .sum(),
+            Statement::Space(expr, column) => {
+                let n = expr.constant(line.number)?;
+                if n < 0 {
+                    return Err(line.error(*column, AsmErrorKind::NegativeSpace(n)));
+                }
+                if n as u64 > MAX_SPACE as u64 {
+                    return Err(line.error(*column, AsmErrorKind::SpaceTooLarge(n)));
+                }
+                n as usize
+            }
+        })
+    }
+
+    fn emit(
+        &self,
+        line: usize,
+        labels: &HashMap<String, usize>,
+        memory: &mut IntcodeMemory,
+    ) -> Result<(), AsmError> {
+        match self {
+            Statement::Instruction { mnemonic, operands } => {
+                let mut word = mnemonic.opcode as Word;
+                let mut place = 100;
+                for operand in operands {
+                    word += place * mode_digit(operand.mode);
+                    place *= 10;
+                }
+                memory.push(word);
+                for operand in operands {
+                    memory.push(operand.expr.evaluate(line, labels)?);
+                }
+            }
+            Statement::Data(items) => {
+                for item in items {
+                    match item {
+                        DataItem::Expr(expr) => memory.push(expr.evaluate(line, labels)?),
+                        DataItem::Str(bytes) => memory.extend(bytes.iter().map(|&b| b as Word)),
+                    }
+                }
+            }
+            Statement::Space(expr, _) => {
+                let n = expr.constant(line)? as usize;
+                memory.extend(std::iter::repeat_n(0, n));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// a single parsed line of source
+struct Line {
+    number: usize,
+    labels: Vec<(String, usize)>,
+    statement: Option<Statement>,
+}
+
+impl Line {
+    fn error(&self, column: usize, kind: AsmErrorKind) -> AsmError {
+        AsmError {
+            line: self.number,
+            column,
+            kind,
+        }
+    }
+
+    fn parse<W>(number: usize, text: &str, isa: &InstructionSet<W>) -> Result<Line, AsmError> {
+        let mut cursor = Cursor {
+            chars: text.chars().collect(),
+            pos: 0,
+            line: number,
+        };
+        let mut line = Line {
+            number,
+            labels: Vec::new(),
+            statement: None,
+        };
+
+        loop {
+            cursor.skip_whitespace();
+            if cursor.at_end() {
+                return Ok(line);
+            }
+            let column = cursor.column();
+            let ident = cursor.identifier()?;
+            cursor.skip_whitespace();
+            if cursor.peek() == Some(':') {
+                cursor.bump();
+                line.labels.push((ident, column));
+                continue;
+            }
+            line.statement = Some(cursor.statement(&ident, column, isa)?);
+            cursor.skip_whitespace();
+            if !cursor.at_end() {
+                return Err(cursor.unexpected());
+            }
+            return Ok(line);
+        }
+    }
+}
+
+struct Cursor {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Cursor {
+    fn column(&self) -> usize {
+        self.pos + 1
+    }
+
+    fn error(&self, kind: AsmErrorKind) -> AsmError {
+        AsmError {
+            line: self.line,
+            column: self.column(),
+            kind,
+        }
+    }
+
+    fn unexpected(&self) -> AsmError {
+        match self.peek() {
+            Some(c) => self.error(AsmErrorKind::UnexpectedChar(c)),
+            None => self.error(AsmErrorKind::UnexpectedEnd),
+        }
+    }
+
+    /// comments are treated as the end of the line
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned().filter(|&c| c != ';')
+    }
+
+    fn at_end(&self) -> bool {
+        self.peek().is_none()
+    }
+
+    fn bump(&mut self) {
+        self.pos += 1;
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn expect(&mut self, want: char) -> Result<(), AsmError> {
+        self.skip_whitespace();
+        if self.peek() == Some(want) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, AsmError> {
+        match self.peek() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+            _ => return Err(self.unexpected()),
+        }
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            self.bump();
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    /// parse a literal, negated if `sign` is `Minus`
+    ///
+    /// The sign is parsed with the digits, so that `-9223372036854775808` is
+    /// in range.
+    fn number(&mut self, sign: Sign) -> Result<Word, AsmError> {
+        let column = self.column();
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric()) {
+            self.bump();
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        let minus = if sign == Sign::Minus { "-" } else { "" };
+        let parsed = if text.starts_with("0x") || text.starts_with("0X") {
+            Word::from_str_radix(&format!("{}{}", minus, &text[2..]), 16)
+        } else {
+            format!("{}{}", minus, text).parse()
+        };
+        parsed.map_err(|_| AsmError {
+            line: self.line,
+            column,
+            kind: AsmErrorKind::InvalidNumber(text),
+        })
+    }
+
+    /// parse a term preceded by `sign`; a literal absorbs its sign
+    fn term(&mut self, sign: Sign) -> Result<(Sign, Term, usize), AsmError> {
+        self.skip_whitespace();
+        let column = self.column();
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                Ok((Sign::Plus, Term::Number(self.number(sign)?), column))
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let ident = self.identifier()?;
+                if ident == REGISTER {
+                    Err(AsmError {
+                        line: self.line,
+                        column,
+                        kind: AsmErrorKind::ReservedName(ident),
+                    })
+                } else {
+                    Ok((sign, Term::Label(ident), column))
+                }
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn sign(&mut self) -> Option<Sign> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('+') => {
+                self.bump();
+                Some(Sign::Plus)
+            }
+            Some('-') => {
+                self.bump();
+                Some(Sign::Minus)
+            }
+            _ => None,
+        }
+    }
+
+    /// parse an expression, which may begin with a sign
+    fn expr(&mut self) -> Result<Expr, AsmError> {
+        let mut terms = Vec::new();
+        let sign = self.sign().unwrap_or(Sign::Plus);
+        terms.push(self.term(sign)?);
+        while let Some(sign) = self.sign() {
+            terms.push(self.term(sign)?);
+        }
+        Ok(Expr(terms))
+    }
+
+    fn operand(&mut self) -> Result<Operand, AsmError> {
+        self.skip_whitespace();
+        let column = self.column();
+        if self.peek() != Some('[') {
+            return Ok(Operand {
+                mode: Mode::Immediate,
+                expr: self.expr()?,
+                column,
+            });
+        }
+        self.bump();
+        self.skip_whitespace();
+
+        // look ahead to distinguish `[rb...]` from `[rbx]`
+        let start = self.pos;
+        let is_relative =
+            self.peek().is_some_and(|c| c.is_ascii_alphabetic()) && self.identifier()? == REGISTER;
+        if !is_relative {
+            self.pos = start;
+        }
+
+        let (mode, expr) = if is_relative {
+            self.skip_whitespace();
+            if self.peek() == Some(']') {
+                (Mode::Relative, Expr::zero())
+            } else {
+                match self.peek() {
+                    Some('+') | Some('-') => {}
+                    _ => return Err(self.unexpected()),
+                }
+                (Mode::Relative, self.expr()?)
+            }
+        } else {
+            (Mode::Position, self.expr()?)
+        };
+        self.expect(']')?;
+        Ok(Operand { mode, expr, column })
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>, AsmError> {
+        let column = self.column();
+        // the opening quote
+        self.bump();
+        let mut bytes = Vec::new();
+        loop {
+            // comments can't begin within strings, so don't use peek here
+            let c = match self.chars.get(self.pos) {
+                Some(&c) => c,
+                None => {
+                    return Err(AsmError {
+                        line: self.line,
+                        column,
+                        kind: AsmErrorKind::UnterminatedString,
+                    })
+                }
+            };
+            self.bump();
+            let c = match c {
+                '"' => return Ok(bytes),
+                '\\' => {
+                    let escaped = self.chars.get(self.pos).cloned();
+                    self.bump();
+                    match escaped {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('0') => '\0',
+                        Some('\\') => '\\',
+                        Some('"') => '"',
+                        Some(c) => {
+                            self.pos -= 1;
+                            return Err(self.error(AsmErrorKind::UnexpectedChar(c)));
+                        }
+                        None => {
+                            return Err(AsmError {
+                                line: self.line,
+                                column,
+                                kind: AsmErrorKind::UnterminatedString,
+                            })
+                        }
+                    }
+                }
+                c => c,
+            };
+            let mut buf = [0; 4];
+            bytes.extend(c.encode_utf8(&mut buf).bytes());
+        }
+    }
+
+    /// parse a comma-separated list, possibly empty
+    fn list<T, F>(&mut self, mut item: F) -> Result<Vec<T>, AsmError>
+    where
+        F: FnMut(&mut Self) -> Result<T, AsmError>,
+    {
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.at_end() {
+            return Ok(items);
+        }
+        items.push(item(self)?);
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(',') {
+                return Ok(items);
+            }
+            self.bump();
+            items.push(item(self)?);
+        }
+    }
+
+    fn statement<W>(
+        &mut self,
+        ident: &str,
+        column: usize,
+        isa: &InstructionSet<W>,
+    ) -> Result<Statement, AsmError> {
+        let line = self.line;
+        let error = |kind| AsmError { line, column, kind };
+        match ident.to_ascii_lowercase().as_str() {
+            "data" => {
+                let items = self.list(|cursor| {
+                    cursor.skip_whitespace();
+                    if cursor.peek() == Some('"') {
+                        cursor.string().map(DataItem::Str)
+                    } else {
+                        cursor.expr().map(DataItem::Expr)
+                    }
+                })?;
+                Ok(Statement::Data(items))
+            }
+            "space" => {
+                self.skip_whitespace();
+                let column = self.column();
+                Ok(Statement::Space(self.expr()?, column))
+            }
+            _ => {
+                let mnemonic = *isa
+                    .by_name(ident)
+                    .ok_or_else(|| error(AsmErrorKind::UnknownMnemonic(ident.to_string())))?;
+                let operands = self.list(Self::operand)?;
+                if operands.len() != mnemonic.arity {
+                    return Err(error(AsmErrorKind::WrongOperandCount {
+                        mnemonic: mnemonic.name,
+                        expected: mnemonic.arity,
+                        found: operands.len(),
+                    }));
+                }
+                if let Some(idx) = mnemonic.writes {
+                    if operands[idx].mode == Mode::Immediate {
+                        return Err(AsmError {
+                            line: self.line,
+                            column: operands[idx].column,
+                            kind: AsmErrorKind::ImmediateWrite(mnemonic.name),
+                        });
+                    }
+                }
+                Ok(Statement::Instruction { mnemonic, operands })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{disasm::render_operand, Intcode};
+
+    #[test]
+    fn test_modes() {
+        let memory = assemble("add [4], 5, [rb-2]\nhlt").unwrap();
+        assert_eq!(memory, vec![21_001, 4, 5, -2, 99]);
+    }
+
+    #[test]
+    fn test_labels_and_data() {
+        let source = "
+            ; echo inputs until we see a zero
+            loop:   in   [value]
+                    jf   [value], done
+                    out  [value]
+                    jt   1, loop
+            done:   hlt
+            value:  data 0
+        ";
+        let memory = assemble(source).unwrap();
+        assert_eq!(memory, vec![3, 11, 1006, 11, 10, 4, 11, 1105, 1, 0, 99, 0]);
+        let mut computer = Intcode::new(memory).using_inputs(&[3, 1, 4, 0]);
+        assert_eq!(computer.run_collect().unwrap(), vec![3, 1, 4]);
+    }
+
+    #[test]
+    fn test_strings_and_space() {
+        let memory = assemble("data \"hi\\n\", end\nspace 2\nend:").unwrap();
+        assert_eq!(memory, vec![104, 105, 10, 6, 0, 0]);
+    }
+
+    #[test]
+    fn test_min_word() {
+        // the disassembler renders Word::MIN as a negative literal
+        let operands: Vec<String> = [Mode::Immediate, Mode::Position, Mode::Relative]
+            .iter()
+            .map(|&mode| render_operand(mode, Word::MIN))
+            .collect();
+        let source = format!(
+            "out {}\nout {}\nout {}\ndata {}, -0x8000000000000000, 1-9223372036854775808",
+            operands[0],
+            operands[1],
+            operands[2],
+            Word::MIN
+        );
+        let memory = assemble(&source).unwrap();
+        assert_eq!(
+            memory,
+            vec![
+                104,
+                Word::MIN,
+                4,
+                Word::MIN,
+                204,
+                Word::MIN,
+                Word::MIN,
+                Word::MIN,
+                Word::MIN + 1
+            ]
+        );
+        assert_eq!(
+            assemble("data -9223372036854775809").unwrap_err().kind,
+            AsmErrorKind::InvalidNumber("9223372036854775809".into())
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = assemble("hlt\n  add 1, 2, 3").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 13));
+        assert_eq!(err.kind, AsmErrorKind::ImmediateWrite("add"));
+
+        let err = assemble("out [nowhere]").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 6));
+        assert_eq!(err.kind, AsmErrorKind::UndefinedLabel("nowhere".into()));
+
+        let err = assemble("jt 1").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 1));
+
+        let err = assemble("out [rb+]").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 9));
+        assert_eq!(err.kind, AsmErrorKind::UnexpectedChar(']'));
+
+        let err = assemble("data 9223372036854775807+1").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 26));
+        assert_eq!(err.kind, AsmErrorKind::Overflow);
+
+        let err = assemble("hlt\nhlt\nx: data -9223372036854775807-x").unwrap_err();
+        assert_eq!(err.kind, AsmErrorKind::Overflow);
+
+        let err = assemble("space 9223372036854775807").unwrap_err();
+        assert_eq!(
+            err.kind,
+            AsmErrorKind::SpaceTooLarge(9_223_372_036_854_775_807)
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! Cache decoded instructions, so loops don't decode the same words repeatedly.
+//!
+//! Each entry holds an instruction's opcode, parameter modes and raw
+//! parameters, keyed by its address. Whenever the machine writes to a cell
+//! which any cached instruction occupies, that entry is discarded, so
+//! self-modifying programs behave exactly as they do without the cache.
+
+use super::{Intcode, Memory, Mode, Opcode};
+
+/// most cells an instruction can occupy: its word and three parameters
+const MAX_LEN: usize = 4;
+
+/// An instruction, decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Decoded<W> {
+    pub opcode: Opcode,
+    pub modes: [Mode; 3],
+    /// raw parameter values; only the first `len - 1` are meaningful
+    pub params: [W; 3],
+    /// quantity of cells the instruction occupies
+    pub len: usize,
+}
+
+/// Decoded instructions, indexed by address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeCache<W> {
+    entries: Vec<Option<Decoded<W>>>,
+    hits: u64,
+    misses: u64,
+    invalidations: u64,
+}
+
+impl<W> Default for DecodeCache<W> {
+    fn default() -> Self {
+        DecodeCache {
+            entries: Vec::new(),
+            hits: 0,
+            misses: 0,
+            invalidations: 0,
+        }
+    }
+}
+
+impl<W: Clone> DecodeCache<W> {
+    pub(super) fn get(&mut self, address: usize) -> Option<Decoded<W>> {
+        match self.entries.get(address) {
+            Some(Some(decoded)) => {
+                self.hits += 1;
+                Some(decoded.clone())
+            }
+            _ => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub(super) fn insert(&mut self, address: usize, decoded: Decoded<W>) {
+        if address >= self.entries.len() {
+            self.entries.resize_with(address + 1, || None);
+        }
+        self.entries[address] = Some(decoded);
+    }
+}
+
+impl<W> DecodeCache<W> {
+    /// discard any instruction which occupies `address`
+    pub(super) fn invalidate(&mut self, address: usize) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let first = address.saturating_sub(MAX_LEN - 1);
+        let last = address.min(self.entries.len().saturating_sub(1));
+        for start in first..=last {
+            let entry = &mut self.entries[start];
+            if entry
+                .as_ref()
+                .is_some_and(|decoded| start + decoded.len > address)
+            {
+                *entry = None;
+                self.invalidations += 1;
+            }
+        }
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// quantity of instructions which were found in the cache
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// quantity of instructions which had to be decoded
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// quantity of cached instructions discarded because their cells were written
+    pub fn invalidations(&self) -> u64 {
+        self.invalidations
+    }
+}
+
+impl<M: Memory> Intcode<M> {
+    /// decode each instruction once, rather than every time it executes
+    pub fn with_decode_cache(mut self) -> Self {
+        self.decode_cache = Some(DecodeCache::default());
+        self
+    }
+
+    /// the decode cache, if this machine has one
+    pub fn decode_cache(&self) -> Option<&DecodeCache<M::Word>> {
+        self.decode_cache.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DecodeCache;
+    use crate::intcode::{asm::assemble, Intcode, State, Word};
+
+    #[test]
+    fn test_self_modifying_code() {
+        // count down from 3, then overwrite the head of the loop with `hlt`
+        let program = assemble(
+            "
+            loop:   add  [n], -1, [n]
+                    out  [n]
+                    jt   [n], loop
+                    add  0, 99, [loop]
+                    jt   1, loop
+            n:      data 3
+            ",
+        )
+        .unwrap();
+        let mut plain = Intcode::new(program.clone());
+        let mut cached = Intcode::new(program).with_decode_cache();
+        loop {
+            let state = cached.resume();
+            assert_eq!(state, plain.resume());
+            if state == Ok(State::Halted) {
+                break;
+            }
+        }
+        assert_eq!(cached.memory(), plain.memory());
+        assert_eq!(cached.retired(), plain.retired());
+
+        let cache = cached.decode_cache().unwrap();
+        assert_eq!(cache.misses(), 6);
+        assert_eq!(cache.hits(), cached.retired() - cache.misses());
+        assert_eq!(cache.invalidations(), 1);
+    }
+
+    #[test]
+    fn test_write_after_restore() {
+        let mut computer = Intcode::new(vec![1101, 1, 1, 0, 99]).with_decode_cache();
+        let snapshot = computer.snapshot();
+        // restoring empties the cache, then the first instruction writes to address 0
+        computer.restore(&snapshot);
+        assert_eq!(computer.resume(), Ok(State::Halted));
+        assert_eq!(computer.memory()[0], 2);
+
+        let mut cache = DecodeCache::<Word>::default();
+        cache.invalidate(0);
+        assert_eq!(cache.invalidations(), 0);
+    }
+
+    #[test]
+    fn test_invalidates_written_code() {
+        // each pass increments the immediate operand of `out`
+        let program = assemble(
+            "
+            loop:   out  0
+                    add  [loop+1], 1, [loop+1]
+                    eq   [loop+1], 3, [done]
+                    jf   [done], loop
+                    hlt
+            done:   data 0
+            ",
+        )
+        .unwrap();
+        let mut computer = Intcode::new(program).with_decode_cache();
+        assert_eq!(computer.run_collect(), Ok(vec![0, 1, 2]));
+        assert_eq!(computer.decode_cache().unwrap().invalidations(), 3);
+    }
+}
@@ -0,0 +1,447 @@
+//! Build a control-flow graph of an Intcode program's basic blocks.
+//!
+//! Code is discovered from the entry points much as the disassembler does.
+//! Each block is a run of instructions which is only entered at its first
+//! instruction and only left after its last. Jumps with immediate-mode targets
+//! become edges to those targets; jumps through position- or relative-mode
+//! operands become edges with unknown targets.
+//!
+//! Compiled Intcode implements subroutines on the relative base. A call
+//! stores its return address into the stack frame and then jumps
+//! unconditionally:
+//!
+//! ```text
+//!         add  back, 0, [rb]
+//!         jt   1, function
+//! back:   ...
+//! ```
+//!
+//! and a return jumps unconditionally through the frame: `jf 0, [rb]`.
+//! These are recognized, so a call has an edge to the function and an edge to
+//! the point at which it resumes, and a return is distinguished from other
+//! dynamic jumps.
+
+use super::{
+    disasm::{decode, render_instruction},
+    isa::{Effect, Flow, InstructionSet, Mnemonic},
+    Arithmetic, Mode, Word,
+};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// execution continues with the following instruction
+    FallThrough,
+    /// a jump is taken
+    Jump,
+    /// a subroutine is called
+    Call,
+    /// execution resumes here once a called subroutine returns
+    ReturnSite,
+    /// a subroutine returns to the address stored in its frame
+    Return,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    /// the address control passes to, or `None` if it's computed at run time
+    pub target: Option<usize>,
+}
+
+impl Edge {
+    fn to(kind: EdgeKind, target: usize) -> Edge {
+        Edge {
+            kind,
+            target: Some(target),
+        }
+    }
+
+    fn unknown(kind: EdgeKind) -> Edge {
+        Edge { kind, target: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: usize,
+    pub mnemonic: Mnemonic,
+    pub modes: Vec<Mode>,
+    pub operands: Vec<Word>,
+}
+
+impl Instruction {
+    /// address of the following instruction
+    pub fn next(&self) -> usize {
+        self.address + 1 + self.mnemonic.arity
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub instructions: Vec<Instruction>,
+    /// where control can go after the last instruction
+    pub successors: Vec<Edge>,
+}
+
+impl Block {
+    /// address of the first instruction
+    pub fn start(&self) -> usize {
+        self.instructions[0].address
+    }
+
+    /// address just past the last instruction
+    pub fn end(&self) -> usize {
+        self.instructions[self.instructions.len() - 1].next()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Cfg {
+    /// basic blocks, by start address
+    pub blocks: BTreeMap<usize, Block>,
+}
+
+impl Cfg {
+    /// the block whose instructions occupy the cell at `address`
+    pub fn block_containing(&self, address: usize) -> Option<&Block> {
+        let (_, block) = self.blocks.range(..=address).next_back()?;
+        (address < block.end()).then_some(block)
+    }
+
+    /// every edge, as `(source block start, edge)`
+    pub fn edges(&self) -> impl '_ + Iterator<Item = (usize, &Edge)> {
+        self.blocks
+            .iter()
+            .flat_map(|(&start, block)| block.successors.iter().map(move |edge| (start, edge)))
+    }
+
+    /// entry points of the subroutines which are called
+    pub fn functions(&self) -> BTreeSet<usize> {
+        self.edges()
+            .filter(|(_, edge)| edge.kind == EdgeKind::Call)
+            .filter_map(|(_, edge)| edge.target)
+            .collect()
+    }
+
+    /// render the graph in Graphviz DOT format
+    pub fn dot(&self) -> String {
+        let labels: BTreeMap<usize, String> = self
+            .blocks
+            .keys()
+            .map(|&start| (start, format!("L{}", start)))
+            .collect();
+        let functions = self.functions();
+
+        let mut out = String::new();
+        writeln!(out, "digraph cfg {{").unwrap();
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        for (start, block) in &self.blocks {
+            let mut label = format!("L{}:\\l", start);
+            for instruction in &block.instructions {
+                let rendered = render_instruction(
+                    &instruction.mnemonic,
+                    &instruction.modes,
+                    &instruction.operands,
+                    &labels,
+                );
+                write!(
+                    label,
+                    "{:>6}  {}\\l",
+                    instruction.address,
+                    escape(&rendered)
+                )
+                .unwrap();
+            }
+            let peripheries = if functions.contains(start) { 2 } else { 1 };
+            writeln!(
+                out,
+                "    b{} [label=\"{}\", peripheries={}];",
+                start, label, peripheries
+            )
+            .unwrap();
+        }
+
+        // edges may lead to addresses at which no instruction could be decoded
+        let invalid: BTreeSet<usize> = self
+            .edges()
+            .filter_map(|(_, edge)| edge.target)
+            .filter(|target| !self.blocks.contains_key(target))
+            .collect();
+        for target in invalid {
+            writeln!(
+                out,
+                "    b{} [label=\"{}: invalid\", style=dashed];",
+                target, target
+            )
+            .unwrap();
+        }
+
+        for (idx, (start, edge)) in self.edges().enumerate() {
+            let target = match edge.target {
+                Some(target) => format!("b{}", target),
+                None => {
+                    writeln!(out, "    unknown{} [label=\"?\", shape=plaintext];", idx).unwrap();
+                    format!("unknown{}", idx)
+                }
+            };
+            let attributes = match edge.kind {
+                EdgeKind::FallThrough => "",
+                EdgeKind::Jump => " [label=\"jump\"]",
+                EdgeKind::Call => " [label=\"call\", style=bold]",
+                EdgeKind::ReturnSite => " [style=dashed]",
+                EdgeKind::Return => " [label=\"ret\", style=dotted]",
+            };
+            writeln!(out, "    b{} -> {}{};", start, target, attributes).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn decode_instruction(
+    memory: &[Word],
+    address: usize,
+    isa: &InstructionSet,
+) -> Option<Instruction> {
+    let (mnemonic, modes) = decode(memory, address, isa)?;
+    let operands = memory[address + 1..=address + mnemonic.arity].to_vec();
+    Some(Instruction {
+        address,
+        mnemonic,
+        modes,
+        operands,
+    })
+}
+
+/// the value `instruction` writes, if it doesn't depend on memory
+fn constant_store(instruction: &Instruction, isa: &InstructionSet) -> Option<Word> {
+    let writes = instruction.mnemonic.writes?;
+    let mut operands = Vec::with_capacity(instruction.mnemonic.arity);
+    for (idx, (&mode, &value)) in instruction
+        .modes
+        .iter()
+        .zip(&instruction.operands)
+        .enumerate()
+    {
+        if idx != writes {
+            if mode != Mode::Immediate {
+                return None;
+            }
+            operands.push(value);
+        }
+    }
+    let (_, handler) = isa.get(instruction.mnemonic.opcode)?;
+    match handler(&operands, Arithmetic::default()) {
+        Ok(Effect::Store(value)) => Some(value),
+        _ => None,
+    }
+}
+
+/// whether the instruction ending just before `jump` pushes the address
+/// following `jump` into the stack frame
+fn pushes_return_address(memory: &[Word], jump: &Instruction, isa: &InstructionSet) -> bool {
+    (jump.address.saturating_sub(4)..jump.address).any(|address| {
+        decode_instruction(memory, address, isa).is_some_and(|store| {
+            store.next() == jump.address
+                && store.mnemonic.flow == Flow::Next
+                && store
+                    .mnemonic
+                    .writes
+                    .is_some_and(|idx| store.modes[idx] == Mode::Relative)
+                && constant_store(&store, isa) == Some(jump.next() as Word)
+        })
+    })
+}
+
+/// where control can go after `instruction`
+fn exits(memory: &[Word], instruction: &Instruction, isa: &InstructionSet) -> Vec<Edge> {
+    let next = instruction.next();
+    let jumps_when = match instruction.mnemonic.flow {
+        Flow::Halt => return Vec::new(),
+        Flow::Next => return vec![Edge::to(EdgeKind::FallThrough, next)],
+        Flow::JumpIf(jumps_when) => jumps_when,
+    };
+    let (test, target) = (instruction.operands[0], instruction.operands[1]);
+    let (may_jump, may_fall) = match instruction.modes[0] {
+        Mode::Immediate => ((test != 0) == jumps_when, (test != 0) != jumps_when),
+        _ => (true, true),
+    };
+    let unconditional = !may_fall;
+
+    let mut out = Vec::with_capacity(2);
+    if may_jump {
+        match instruction.modes[1] {
+            Mode::Immediate if target < 0 => {}
+            Mode::Immediate if unconditional && pushes_return_address(memory, instruction, isa) => {
+                out.push(Edge::to(EdgeKind::Call, target as usize));
+                out.push(Edge::to(EdgeKind::ReturnSite, next));
+            }
+            Mode::Immediate => out.push(Edge::to(EdgeKind::Jump, target as usize)),
+            Mode::Relative if unconditional => out.push(Edge::unknown(EdgeKind::Return)),
+            _ => out.push(Edge::unknown(EdgeKind::Jump)),
+        }
+    }
+    if may_fall {
+        out.push(Edge::to(EdgeKind::FallThrough, next));
+    }
+    out
+}
+
+/// Build the control-flow graph of a program, starting from address 0.
+pub fn control_flow(memory: &[Word]) -> Cfg {
+    control_flow_with(memory, &[0], &InstructionSet::standard())
+}
+
+/// Build the control-flow graph of a program written for the instructions of
+/// `isa`, starting from each of the given entry points.
+pub fn control_flow_with(memory: &[Word], entry_points: &[usize], isa: &InstructionSet) -> Cfg {
+    let mut instructions = BTreeMap::new();
+    let mut leaders: BTreeSet<usize> = entry_points.iter().cloned().collect();
+    let mut queue: VecDeque<usize> = entry_points.iter().cloned().collect();
+
+    while let Some(address) = queue.pop_front() {
+        if instructions.contains_key(&address) {
+            continue;
+        }
+        let instruction = match decode_instruction(memory, address, isa) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        let edges = exits(memory, &instruction, isa);
+        let falls_through = edges == [Edge::to(EdgeKind::FallThrough, instruction.next())];
+        for target in edges.iter().filter_map(|edge| edge.target) {
+            if !falls_through {
+                leaders.insert(target);
+            }
+            queue.push_back(target);
+        }
+        instructions.insert(address, (instruction, edges));
+    }
+
+    let mut blocks = BTreeMap::new();
+    let mut current: Option<Block> = None;
+    for (address, (instruction, edges)) in instructions {
+        let continues = current
+            .as_ref()
+            .is_some_and(|block| block.successors == [Edge::to(EdgeKind::FallThrough, address)]);
+        if !continues || leaders.contains(&address) {
+            if let Some(block) = current.take() {
+                blocks.insert(block.start(), block);
+            }
+        }
+        let block = current.get_or_insert_with(|| Block {
+            instructions: Vec::new(),
+            successors: Vec::new(),
+        });
+        block.instructions.push(instruction);
+        block.successors = edges;
+    }
+    if let Some(block) = current {
+        blocks.insert(block.start(), block);
+    }
+
+    Cfg { blocks }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{asm::assemble, Intcode};
+
+    fn subroutine() -> Vec<Word> {
+        assemble(
+            "
+                    arb  stack
+                    in   [x]
+                    add  back, 0, [rb]
+                    jt   1, double
+            back:   out  [x]
+                    hlt
+            ; double x, then make it odd
+            double: arb  1
+                    mul  [x], 2, [x]
+                    jt   [x], skip
+                    add  [x], 1, [x]
+            skip:   arb  -1
+                    jf   0, [rb]
+            x:      data 0
+            stack:  data 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_subroutine() {
+        let memory = subroutine();
+        assert_eq!(
+            Intcode::new(memory.clone())
+                .using_inputs(&[0])
+                .run_collect(),
+            Ok(vec![1])
+        );
+
+        let cfg = control_flow(&memory);
+        let starts: Vec<_> = cfg.blocks.keys().cloned().collect();
+        assert_eq!(starts, vec![0, 11, 14, 23, 27]);
+        let successors = |start| cfg.blocks[&start].successors.clone();
+        assert_eq!(
+            successors(0),
+            vec![
+                Edge::to(EdgeKind::Call, 14),
+                Edge::to(EdgeKind::ReturnSite, 11),
+            ]
+        );
+        assert_eq!(successors(11), vec![]);
+        assert_eq!(
+            successors(14),
+            vec![
+                Edge::to(EdgeKind::Jump, 27),
+                Edge::to(EdgeKind::FallThrough, 23),
+            ]
+        );
+        assert_eq!(successors(23), vec![Edge::to(EdgeKind::FallThrough, 27)]);
+        assert_eq!(successors(27), vec![Edge::unknown(EdgeKind::Return)]);
+        assert_eq!(cfg.functions(), vec![14].into_iter().collect());
+        assert_eq!(cfg.block_containing(19).map(Block::start), Some(14));
+        assert_eq!(cfg.blocks[&14].end(), 23);
+    }
+
+    #[test]
+    fn test_dynamic_jump() {
+        // a jump through memory which isn't a return
+        let memory = assemble(
+            "
+                    in   [target]
+                    jt   1, [target]
+            target: data 0
+            ",
+        )
+        .unwrap();
+        let cfg = control_flow(&memory);
+        assert_eq!(cfg.blocks.len(), 1);
+        assert_eq!(
+            cfg.blocks[&0].successors,
+            vec![Edge::unknown(EdgeKind::Jump)]
+        );
+    }
+
+    #[test]
+    fn test_dot() {
+        let dot = control_flow(&subroutine()).dot();
+        assert!(dot.starts_with("digraph cfg {\n"));
+        assert!(dot.contains("    b0 -> b14 [label=\"call\", style=bold];\n"));
+        assert!(dot.contains("    b0 -> b11 [style=dashed];\n"));
+        assert!(dot.contains("    b27 -> unknown"));
+        assert!(dot.contains("b14 [label=\"L14:\\l    14  arb  1\\l"));
+        assert!(dot.contains("    20  jt   [32], L27\\l"));
+        assert!(dot.contains("peripheries=2"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
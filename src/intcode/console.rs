@@ -0,0 +1,231 @@
+//! Play ASCII-speaking Intcode programs from a terminal.
+//!
+//! The program's text is printed as it arrives, and each line read while the
+//! program waits for input is sent to it. Outputs outside the ASCII range are
+//! printed as numbers on their own lines.
+//!
+//! Lines beginning with `!` are commands to the console rather than input:
+//! see `HELP`.
+
+use super::{
+    ascii::{AsciiComputer, Event},
+    Intcode,
+};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+pub const HELP: &str = "console commands:
+  !!         send the previous line again
+  !N         send line N of the history again
+  !history   list the lines sent so far
+  !help      show this message";
+
+pub struct Console {
+    computer: AsciiComputer,
+    history: Vec<String>,
+    script: VecDeque<String>,
+    transcript: Option<Box<dyn Write>>,
+}
+
+impl Console {
+    pub fn new(computer: Intcode) -> Console {
+        Console {
+            computer: AsciiComputer::new(computer),
+            history: Vec::new(),
+            script: VecDeque::new(),
+            transcript: None,
+        }
+    }
+
+    /// send these lines before reading any from the terminal
+    pub fn with_script(mut self, lines: impl IntoIterator<Item = String>) -> Self {
+        self.script.extend(lines);
+        self
+    }
+
+    /// record the session's input and output
+    pub fn with_transcript(mut self, transcript: Box<dyn Write>) -> Self {
+        self.transcript = Some(transcript);
+        self
+    }
+
+    /// the lines sent to the program so far
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    pub fn computer(&self) -> &AsciiComputer {
+        &self.computer
+    }
+
+    fn log(&mut self, text: &str) -> io::Result<()> {
+        match &mut self.transcript {
+            Some(transcript) => writeln!(transcript, "{}", text),
+            None => Ok(()),
+        }
+    }
+
+    /// expand a history command, or explain why it can't be
+    fn recall(&self, command: &str) -> Result<String, String> {
+        let idx = match command {
+            "!" => self.history.len(),
+            _ => command
+                .parse::<usize>()
+                .map_err(|_| format!("unknown console command: !{}", command))?,
+        };
+        idx.checked_sub(1)
+            .and_then(|idx| self.history.get(idx))
+            .cloned()
+            .ok_or_else(|| format!("no line {} in history", idx))
+    }
+
+    /// the next line to send, or `None` once the input is exhausted
+    fn next_line<R, W>(&mut self, input: &mut R, out: &mut W) -> io::Result<Option<String>>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        if let Some(line) = self.script.pop_front() {
+            writeln!(out, "{}", line)?;
+            return Ok(Some(line));
+        }
+        loop {
+            out.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            let line = line.trim_end_matches(&['\r', '\n'][..]);
+            let command = match line.strip_prefix('!') {
+                Some(command) => command,
+                None => return Ok(Some(line.to_string())),
+            };
+            match command {
+                "history" => {
+                    for (idx, line) in self.history.iter().enumerate() {
+                        writeln!(out, "{:>5}  {}", idx + 1, line)?;
+                    }
+                }
+                "help" => writeln!(out, "{}", HELP)?,
+                _ => match self.recall(command) {
+                    Ok(line) => {
+                        writeln!(out, "{}", line)?;
+                        return Ok(Some(line));
+                    }
+                    Err(err) => writeln!(out, "{}", err)?,
+                },
+            }
+        }
+    }
+
+    /// run the program until it halts or the input is exhausted
+    pub fn run<R, W>(&mut self, mut input: R, mut out: W) -> io::Result<()>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        loop {
+            let text = match self.computer.resume() {
+                Ok(Event::Line(line)) => line,
+                Ok(Event::Value(value)) => value.to_string(),
+                Ok(Event::NeedsInput) => match self.next_line(&mut input, &mut out)? {
+                    Some(line) => {
+                        self.log(&line)?;
+                        match self.computer.send_line(&line) {
+                            Ok(()) => self.history.push(line),
+                            Err(err) => {
+                                // the program still wants input, so it asks again
+                                writeln!(out, "{}", err)?;
+                                self.log(&err.to_string())?;
+                            }
+                        }
+                        continue;
+                    }
+                    None => break,
+                },
+                Ok(Event::Halted) => break,
+                Err(err) => {
+                    let message = format!("error: {}", err);
+                    writeln!(out, "{}", message)?;
+                    self.log(&message)?;
+                    break;
+                }
+            };
+            writeln!(out, "{}", text)?;
+            self.log(&text)?;
+        }
+        if let Some(transcript) = &mut self.transcript {
+            transcript.flush()?;
+        }
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_session() {
+        // greet with a number, then echo forever
+        let program = assemble(
+            "
+                    out  1000
+            loop:   in   [c]
+                    out  [c]
+                    jt   1, loop
+            c:      data 0
+            ",
+        )
+        .unwrap();
+        let transcript = Shared::default();
+        let mut console = Console::new(Intcode::new(program))
+            .with_script(vec!["hi".to_string()])
+            .with_transcript(Box::new(transcript.clone()));
+
+        let input = "there\n!!\n!1\n!7\n!history\n";
+        let mut out = Vec::new();
+        console.run(input.as_bytes(), &mut out).unwrap();
+
+        assert_eq!(console.history(), &["hi", "there", "there", "hi"]);
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            "1000
+hi
+hi
+there
+there
+there
+hi
+hi
+no line 7 in history
+    1  hi
+    2  there
+    3  there
+    4  hi
+"
+        );
+        let transcript = String::from_utf8(transcript.0.borrow().clone()).unwrap();
+        assert_eq!(
+            transcript,
+            "1000\nhi\nhi\nthere\nthere\nthere\nthere\nhi\nhi\n"
+        );
+    }
+}
@@ -0,0 +1,630 @@
+//! An interactive debugger for Intcode programs.
+//!
+//! Breakpoints and watchpoints trigger before the instruction responsible
+//! executes, so the machine can be inspected in the state which caused them.
+//!
+//! If the machine keeps an undo log, execution can also run backwards: one
+//! instruction at a time, to just before the previous write of an address,
+//! or to just before the most recent output.
+
+use super::{
+    disasm::render_at,
+    undo::{Io, UndoEntry},
+    InstructionSet, Intcode, IntcodeError, State, Word,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+const PROMPT: &str = "(intcode) ";
+
+const HELP: &str = "commands:
+  break ADDR         (b)  stop before executing the instruction at ADDR
+  watch ADDR [r|w]   (w)  stop before ADDR is read or written (default: either)
+  delete ADDR        (d)  remove the breakpoint and watchpoint at ADDR
+  info               (i)  list breakpoints and watchpoints
+  step [N]           (s)  execute N instructions (default: 1)
+  continue           (c)  run until something needs attention
+  back [N]           (bs) undo N instructions (default: 1)
+  rwrite ADDR             run backwards to just before the previous write of ADDR
+  routput                 run backwards to just before the most recent output
+  input VALUE...          queue inputs for the program
+  regs               (r)  show the registers
+  x ADDR [N]              examine N words of memory (default: 8)
+  list [ADDR] [N]    (l)  disassemble N instructions from ADDR (default: ip, 8)
+  help               (h)  show this message
+  quit               (q)  leave the debugger";
+
+/// which memory accesses trigger a watchpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Read,
+    Write,
+    Access,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl Watch {
+    fn matches(self, access: Access) -> bool {
+        matches!(
+            (self, access),
+            (Watch::Access, _) | (Watch::Read, Access::Read) | (Watch::Write, Access::Write)
+        )
+    }
+}
+
+impl FromStr for Watch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "r" => Ok(Watch::Read),
+            "w" => Ok(Watch::Write),
+            "rw" => Ok(Watch::Access),
+            _ => Err(format!("unknown watch kind: {} (want r, w, or rw)", s)),
+        }
+    }
+}
+
+/// why the debugger stopped executing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    /// the instruction at this address is about to execute
+    Breakpoint(usize),
+    /// the instruction at the current ip is about to access a watched cell
+    Watchpoint {
+        address: usize,
+        access: Access,
+    },
+    /// a single instruction executed without incident
+    Stepped,
+    NeedsInput,
+    Output(Word),
+    Halted,
+    Error(IntcodeError),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Breakpoint(address) => write!(f, "breakpoint at {}", address),
+            Stop::Watchpoint { address, access } => {
+                let verb = match access {
+                    Access::Read => "read",
+                    Access::Write => "written",
+                };
+                write!(f, "watchpoint: {} is about to be {}", address, verb)
+            }
+            Stop::Stepped => write!(f, "stepped"),
+            Stop::NeedsInput => write!(f, "program needs input"),
+            Stop::Output(value) => write!(f, "output: {}", value),
+            Stop::Halted => write!(f, "program halted"),
+            Stop::Error(err) => write!(f, "error: {}", err),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Debugger {
+    computer: Intcode,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeMap<usize, Watch>,
+    /// the ip of the most recent breakpoint or watchpoint stop
+    stopped_at: Option<usize>,
+}
+
+impl Debugger {
+    pub fn new(computer: Intcode) -> Debugger {
+        Debugger {
+            computer,
+            ..Debugger::default()
+        }
+    }
+
+    pub fn computer(&self) -> &Intcode {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Intcode {
+        &mut self.computer
+    }
+
+    /// returns false if the breakpoint already existed
+    pub fn add_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    /// returns false if there was no such breakpoint
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// watch a memory cell, replacing any existing watchpoint on it
+    pub fn watch(&mut self, address: usize, watch: Watch) {
+        self.watchpoints.insert(address, watch);
+    }
+
+    /// returns false if there was no such watchpoint
+    pub fn unwatch(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address).is_some()
+    }
+
+    pub fn breakpoints(&self) -> impl '_ + Iterator<Item = usize> {
+        self.breakpoints.iter().cloned()
+    }
+
+    pub fn watchpoints(&self) -> impl '_ + Iterator<Item = (usize, Watch)> {
+        self.watchpoints
+            .iter()
+            .map(|(&address, &watch)| (address, watch))
+    }
+
+    /// the watchpoint which the next instruction would trigger, if any
+    fn triggered_watchpoint(&self) -> Option<Stop> {
+        if self.watchpoints.is_empty() {
+            return None;
+        }
+        // if the accesses can't be computed, the instruction will fail,
+        // and the failure is more interesting than any watchpoint
+        let accesses = self.computer.accesses().ok()?;
+        let reads = accesses.reads.into_iter().map(|a| (a, Access::Read));
+        let writes = accesses.write.into_iter().map(|a| (a, Access::Write));
+        reads.chain(writes).find_map(|(address, access)| {
+            self.watchpoints
+                .get(&address)
+                .filter(|watch| watch.matches(access))
+                .map(|_| Stop::Watchpoint { address, access })
+        })
+    }
+
+    /// execute a single instruction, ignoring breakpoints and watchpoints
+    pub fn step(&mut self) -> Stop {
+        self.stopped_at = None;
+        match self.computer.step() {
+            Ok(None) => Stop::Stepped,
+            Ok(Some(State::NeedsInput)) => Stop::NeedsInput,
+            Ok(Some(State::Output(value))) => Stop::Output(value),
+            Ok(Some(State::Halted)) => Stop::Halted,
+            Err(err) => Stop::Error(err),
+        }
+    }
+
+    /// run until a breakpoint or watchpoint triggers, or the program produces
+    /// output, needs input, halts, or fails
+    ///
+    /// When resuming from a breakpoint or watchpoint, the instruction which
+    /// triggered it executes without triggering it again, so that execution
+    /// makes progress.
+    pub fn run(&mut self) -> Stop {
+        let mut resuming = self.stopped_at.take() == Some(self.computer.ip());
+        loop {
+            if !resuming {
+                let ip = self.computer.ip();
+                let stop = if self.breakpoints.contains(&ip) {
+                    Some(Stop::Breakpoint(ip))
+                } else {
+                    self.triggered_watchpoint()
+                };
+                if let Some(stop) = stop {
+                    self.stopped_at = Some(ip);
+                    return stop;
+                }
+            }
+            resuming = false;
+            match self.step() {
+                Stop::Stepped => {}
+                stop => return stop,
+            }
+        }
+    }
+
+    /// undo the most recently executed instruction
+    ///
+    /// Returns false if there is no history to undo.
+    pub fn step_back(&mut self) -> bool {
+        let undone = self.computer.step_back().is_some();
+        // resuming shouldn't immediately re-trigger a stop here
+        self.stopped_at = Some(self.computer.ip());
+        undone
+    }
+
+    /// undo instructions until the most recently undone one is the first
+    /// match for `predicate`, searching backwards through the history
+    ///
+    /// If nothing in the history matches, the machine is unchanged and this
+    /// returns false.
+    fn rewind_until<F>(&mut self, predicate: F) -> bool
+    where
+        F: Fn(&UndoEntry) -> bool,
+    {
+        let found = self
+            .computer
+            .undo_log()
+            .is_some_and(|log| log.iter().any(&predicate));
+        if !found {
+            return false;
+        }
+        while let Some(entry) = self.computer.step_back() {
+            if predicate(&entry) {
+                break;
+            }
+        }
+        self.stopped_at = Some(self.computer.ip());
+        true
+    }
+
+    /// run backwards to just before the previous write to `address`
+    pub fn rewind_to_write(&mut self, address: usize) -> bool {
+        self.rewind_until(|entry| entry.write.map(|(written, _)| written) == Some(address))
+    }
+
+    /// run backwards to just before the most recent output
+    pub fn rewind_to_output(&mut self) -> bool {
+        self.rewind_until(|entry| matches!(entry.io, Some(Io::Output(_))))
+    }
+
+    fn show_location<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let ip = self.computer.ip();
+        let memory = self.computer.memory();
+        let isa = self.computer.instruction_set();
+        let instruction = render_at(memory, ip, isa).unwrap_or_else(|| match memory.get(ip) {
+            Some(word) => format!("data {}", word),
+            None => "(past end of memory)".to_string(),
+        });
+        writeln!(out, "{:>6}  {}", ip, instruction)
+    }
+
+    /// report a stop to the user; returns true if execution should continue
+    fn report<W: Write>(stop: &Stop, out: &mut W) -> io::Result<bool> {
+        match stop {
+            Stop::Stepped => Ok(true),
+            Stop::Output(_) => {
+                writeln!(out, "{}", stop)?;
+                Ok(true)
+            }
+            _ => {
+                writeln!(out, "{}", stop)?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// execute a single command; returns false if the user wants to quit
+    fn command<W: Write>(&mut self, command: &str, args: &[&str], out: &mut W) -> io::Result<bool> {
+        macro_rules! arg {
+            ($idx:expr, $name:expr) => {
+                match parse_arg(args, $idx, $name) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        writeln!(out, "{}", err)?;
+                        return Ok(true);
+                    }
+                }
+            };
+        }
+        macro_rules! opt_arg {
+            ($idx:expr, $name:expr, $default:expr) => {
+                if args.len() > $idx {
+                    arg!($idx, $name)
+                } else {
+                    $default
+                }
+            };
+        }
+
+        match command {
+            "h" | "help" => writeln!(out, "{}", HELP)?,
+            "q" | "quit" => return Ok(false),
+            "b" | "break" => {
+                let address = arg!(0, "ADDR");
+                self.add_breakpoint(address);
+                writeln!(out, "breakpoint at {}", address)?;
+            }
+            "w" | "watch" => {
+                let address = arg!(0, "ADDR");
+                let watch = opt_arg!(1, "KIND", Watch::Access);
+                self.watch(address, watch);
+                writeln!(out, "watching {} ({:?})", address, watch)?;
+            }
+            "d" | "delete" => {
+                let address = arg!(0, "ADDR");
+                let removed_break = self.remove_breakpoint(address);
+                let removed_watch = self.unwatch(address);
+                if !(removed_break || removed_watch) {
+                    writeln!(out, "nothing to delete at {}", address)?;
+                }
+            }
+            "i" | "info" => {
+                for address in self.breakpoints() {
+                    writeln!(out, "breakpoint at {}", address)?;
+                }
+                for (address, watch) in self.watchpoints() {
+                    writeln!(out, "watching {} ({:?})", address, watch)?;
+                }
+            }
+            "s" | "step" => {
+                let steps: usize = opt_arg!(0, "N", 1);
+                for _ in 0..steps {
+                    let stop = self.step();
+                    if !Self::report(&stop, out)? {
+                        break;
+                    }
+                }
+                self.show_location(out)?;
+            }
+            "c" | "continue" => {
+                while Self::report(&self.run(), out)? {}
+                self.show_location(out)?;
+            }
+            "bs" | "back" => {
+                let steps: usize = opt_arg!(0, "N", 1);
+                for _ in 0..steps {
+                    if !self.step_back() {
+                        writeln!(out, "no more history")?;
+                        break;
+                    }
+                }
+                self.show_location(out)?;
+            }
+            "rwrite" => {
+                let address = arg!(0, "ADDR");
+                if self.rewind_to_write(address) {
+                    self.show_location(out)?;
+                } else {
+                    writeln!(out, "no write to {} in history", address)?;
+                }
+            }
+            "routput" => {
+                if self.rewind_to_output() {
+                    self.show_location(out)?;
+                } else {
+                    writeln!(out, "no output in history")?;
+                }
+            }
+            "input" => {
+                for idx in 0..args.len() {
+                    let value: Word = arg!(idx, "VALUE");
+                    self.computer.push_input(value);
+                }
+            }
+            "r" | "regs" => {
+                writeln!(
+                    out,
+                    "ip: {}  rb: {}  halted: {}  retired: {}  pending inputs: {:?}",
+                    self.computer.ip(),
+                    self.computer.relative_base(),
+                    self.computer.is_halted(),
+                    self.computer.retired(),
+                    self.computer.pending_inputs(),
+                )?;
+            }
+            "x" => {
+                let address: usize = arg!(0, "ADDR");
+                let quantity: usize = opt_arg!(1, "N", 8);
+                let end = match address.checked_add(quantity) {
+                    Some(end) => end,
+                    None => {
+                        writeln!(
+                            out,
+                            "{} words from {} is beyond any address",
+                            quantity, address
+                        )?;
+                        return Ok(true);
+                    }
+                };
+                let memory = self.computer.memory();
+                for row in (address..end).step_by(8) {
+                    let words = (row..row.saturating_add(8).min(end))
+                        .map(|a| memory.get(a).cloned().unwrap_or_default().to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    writeln!(out, "{:>6}: {}", row, words)?;
+                }
+            }
+            "l" | "list" => {
+                let mut address: usize = opt_arg!(0, "ADDR", self.computer.ip());
+                let quantity: usize = opt_arg!(1, "N", 8);
+                let memory = self.computer.memory();
+                let isa = self.computer.instruction_set();
+                for _ in 0..quantity {
+                    if address >= memory.len() {
+                        break;
+                    }
+                    match render_at(memory, address, isa) {
+                        Some(instruction) => {
+                            writeln!(out, "{:>6}  {}", address, instruction)?;
+                            // safe: render_at only succeeds for valid instructions
+                            address += instruction_len(memory[address], isa);
+                        }
+                        None => {
+                            writeln!(out, "{:>6}  data {}", address, memory[address])?;
+                            address += 1;
+                        }
+                    }
+                }
+            }
+            _ => writeln!(out, "unknown command: {} (try `help`)", command)?,
+        }
+        Ok(true)
+    }
+
+    /// drive the debugger from a line-oriented command stream
+    pub fn repl<R, W>(&mut self, input: R, mut out: W) -> io::Result<()>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        self.show_location(&mut out)?;
+        write!(out, "{}", PROMPT)?;
+        out.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            if let Some(command) = words.next() {
+                let args: Vec<&str> = words.collect();
+                if !self.command(command, &args, &mut out)? {
+                    return Ok(());
+                }
+            }
+            write!(out, "{}", PROMPT)?;
+            out.flush()?;
+        }
+        writeln!(out)
+    }
+}
+
+/// quantity of words occupied by the valid instruction beginning with `word`
+fn instruction_len(word: Word, isa: &InstructionSet) -> usize {
+    Intcode::destructure(word)
+        .ok()
+        .and_then(|(opcode, _, _, _)| isa.by_opcode(opcode))
+        .map_or(1, |mnemonic| 1 + mnemonic.arity)
+}
+
+fn parse_arg<T: FromStr>(args: &[&str], idx: usize, name: &str) -> Result<T, String> {
+    let arg = args
+        .get(idx)
+        .ok_or_else(|| format!("missing argument: {}", name))?;
+    arg.parse()
+        .map_err(|_| format!("could not parse {} as {}", arg, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    fn debugger() -> Debugger {
+        // sum inputs until a zero, then output the total
+        let program = assemble(
+            "
+            loop:   in   [x]
+                    jf   [x], done
+                    add  [x], [total], [total]
+                    jt   1, loop
+            done:   out  [total]
+                    hlt
+            x:      data 0
+            total:  data 0
+            ",
+        )
+        .unwrap();
+        Debugger::new(Intcode::new(program))
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut debugger = debugger();
+        debugger.add_breakpoint(5);
+        debugger.computer_mut().push_input(3);
+        assert_eq!(debugger.run(), Stop::Breakpoint(5));
+        // continuing from a breakpoint makes progress
+        assert_eq!(debugger.run(), Stop::NeedsInput);
+        debugger.computer_mut().push_input(0);
+        assert_eq!(debugger.run(), Stop::Output(3));
+        assert_eq!(debugger.run(), Stop::Halted);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut debugger = debugger();
+        debugger.watch(16, Watch::Write);
+        debugger.computer_mut().push_input(4);
+        debugger.computer_mut().push_input(0);
+        assert_eq!(
+            debugger.run(),
+            Stop::Watchpoint {
+                address: 16,
+                access: Access::Write
+            }
+        );
+        assert_eq!(debugger.computer().ip(), 5);
+
+        debugger.watch(16, Watch::Read);
+        assert_eq!(
+            debugger.run(),
+            Stop::Watchpoint {
+                address: 16,
+                access: Access::Read
+            }
+        );
+        assert_eq!(debugger.computer().ip(), 12);
+        assert_eq!(debugger.computer().memory()[16], 4);
+    }
+
+    #[test]
+    fn test_watchpoint_on_first_instruction() {
+        let mut debugger = debugger();
+        debugger.watch(15, Watch::Access);
+        assert_eq!(
+            debugger.run(),
+            Stop::Watchpoint {
+                address: 15,
+                access: Access::Write
+            }
+        );
+        assert_eq!(debugger.computer().ip(), 0);
+    }
+
+    #[test]
+    fn test_repl() {
+        let mut debugger = debugger();
+        let script =
+            "break 12\ninput 2 5 0\ncontinue\nregs\nx 15 2\nx 1 18446744073709551615\nstep 2\nq\n";
+        let mut out = Vec::new();
+        debugger.repl(script.as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let expect = "     0  in   [15]
+(intcode) breakpoint at 12
+(intcode) (intcode) breakpoint at 12
+    12  out  [16]
+(intcode) ip: 12  rb: 0  halted: false  retired: 10  pending inputs: []
+(intcode)     15: 0 7
+(intcode) 18446744073709551615 words from 1 is beyond any address
+(intcode) output: 7
+program halted
+    14  hlt
+(intcode) ";
+        assert_eq!(out, expect);
+    }
+
+    #[test]
+    fn test_reverse() {
+        let mut debugger = debugger();
+        *debugger.computer_mut() = debugger.computer().clone().with_undo_log(1000);
+        for input in &[2, 5, 0] {
+            debugger.computer_mut().push_input(*input);
+        }
+        assert_eq!(debugger.run(), Stop::Output(7));
+        assert_eq!(debugger.run(), Stop::Halted);
+
+        assert!(debugger.rewind_to_output());
+        assert_eq!(debugger.computer().ip(), 12);
+        assert!(!debugger.computer().is_halted());
+
+        // the last write to total added 5 to 2
+        assert!(debugger.rewind_to_write(16));
+        assert_eq!(debugger.computer().ip(), 5);
+        assert_eq!(debugger.computer().memory()[16], 2);
+        assert_eq!(debugger.computer().memory()[15], 5);
+
+        // undoing the input returns it to the queue
+        assert!(debugger.rewind_to_write(15));
+        assert_eq!(debugger.computer().ip(), 0);
+        assert_eq!(debugger.computer().pending_inputs(), &[5, 0]);
+
+        assert!(!debugger.rewind_to_output());
+        assert_eq!(debugger.computer().ip(), 0);
+
+        // replaying produces the same result
+        assert_eq!(debugger.run(), Stop::Output(7));
+        assert!(debugger.step_back());
+        assert_eq!(debugger.run(), Stop::Output(7));
+    }
+}
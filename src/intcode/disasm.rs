@@ -0,0 +1,314 @@
+//! Disassemble Intcode memory into a readable listing.
+//!
+//! Code is discovered by following control flow from the entry points,
+//! according to each instruction's `Flow`: every instruction falls through to
+//! its successor, except that halts stop, and conditional jumps with
+//! immediate-mode targets also continue at their targets. Jumps whose
+//! condition is an immediate constant are resolved, so `jt 1, x` never falls
+//! through and `jt 0, x` never jumps. Jumps to position- or relative-mode
+//! targets can't be resolved statically, so code reached only through them
+//! appears as data. Cells which are never reached are emitted as data.
+
+use super::{
+    asm::REGISTER,
+    isa::{Flow, InstructionSet, Mnemonic},
+    Intcode, IntcodeMemory, Mode, Word,
+};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+
+/// maximum quantity of words per line of data in a listing
+const DATA_PER_LINE: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Instruction {
+        address: usize,
+        mnemonic: Mnemonic,
+        modes: Vec<Mode>,
+        operands: Vec<Word>,
+    },
+    Data {
+        address: usize,
+        words: Vec<Word>,
+    },
+}
+
+impl Item {
+    pub fn address(&self) -> usize {
+        match self {
+            Item::Instruction { address, .. } | Item::Data { address, .. } => *address,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Disassembly {
+    pub items: Vec<Item>,
+    /// names for addresses which are jump targets
+    pub labels: BTreeMap<usize, String>,
+}
+
+/// decode the instruction at `address`, if there is a valid one there
+pub fn decode<W>(
+    memory: &[Word],
+    address: usize,
+    isa: &InstructionSet<W>,
+) -> Option<(Mnemonic, Vec<Mode>)> {
+    let (opcode, p1, p2, p3) = Intcode::destructure(*memory.get(address)?).ok()?;
+    let mnemonic = *isa.by_opcode(opcode)?;
+    if address + mnemonic.arity >= memory.len() {
+        return None;
+    }
+    let modes = [p1, p2, p3][..mnemonic.arity].to_vec();
+    if let Some(idx) = mnemonic.writes {
+        if modes[idx] == Mode::Immediate {
+            return None;
+        }
+    }
+    Some((mnemonic, modes))
+}
+
+/// compute the static successors of the decoded instruction at `address`
+fn successors(memory: &[Word], address: usize, mnemonic: &Mnemonic, modes: &[Mode]) -> Vec<usize> {
+    let next = address + 1 + mnemonic.arity;
+    match mnemonic.flow {
+        Flow::Halt => Vec::new(),
+        Flow::JumpIf(jumps_when) => {
+            let test = memory[address + 1];
+            let target = memory[address + 2];
+            let mut out = Vec::with_capacity(2);
+            let (may_jump, may_fall) = match modes[0] {
+                Mode::Immediate => ((test != 0) == jumps_when, (test != 0) != jumps_when),
+                _ => (true, true),
+            };
+            if may_jump && modes[1] == Mode::Immediate && target >= 0 {
+                out.push(target as usize);
+            }
+            if may_fall {
+                out.push(next);
+            }
+            out
+        }
+        Flow::Next => vec![next],
+    }
+}
+
+/// Disassemble a program, following control flow from address 0.
+pub fn disassemble(memory: &IntcodeMemory) -> Disassembly {
+    disassemble_from(memory, &[0])
+}
+
+/// Disassemble a program, following control flow from each of the given entry points.
+pub fn disassemble_from(memory: &IntcodeMemory, entry_points: &[usize]) -> Disassembly {
+    disassemble_with(memory, entry_points, &InstructionSet::<Word>::standard())
+}
+
+/// Disassemble a program written for the instructions of `isa`, following
+/// control flow from each of the given entry points.
+pub fn disassemble_with<W>(
+    memory: &IntcodeMemory,
+    entry_points: &[usize],
+    isa: &InstructionSet<W>,
+) -> Disassembly {
+    let mut instructions = BTreeMap::new();
+    let mut jump_targets = BTreeSet::new();
+    let mut queue: VecDeque<usize> = entry_points.iter().cloned().collect();
+
+    while let Some(address) = queue.pop_front() {
+        if instructions.contains_key(&address) {
+            continue;
+        }
+        let (mnemonic, modes) = match decode(memory, address, isa) {
+            Some(decoded) => decoded,
+            None => continue,
+        };
+        let next = address + 1 + mnemonic.arity;
+        for successor in successors(memory, address, &mnemonic, &modes) {
+            if successor != next {
+                jump_targets.insert(successor);
+            }
+            queue.push_back(successor);
+        }
+        instructions.insert(address, (mnemonic, modes));
+    }
+
+    let labels: BTreeMap<usize, String> = jump_targets
+        .into_iter()
+        .filter(|target| instructions.contains_key(target))
+        .map(|target| (target, format!("L{}", target)))
+        .collect();
+
+    // linear sweep: anything which isn't covered by a reachable instruction is data
+    let mut items = Vec::new();
+    let mut address = 0;
+    while address < memory.len() {
+        if let Some((mnemonic, modes)) = instructions.get(&address) {
+            let operands = memory[address + 1..=address + mnemonic.arity].to_vec();
+            items.push(Item::Instruction {
+                address,
+                mnemonic: *mnemonic,
+                modes: modes.clone(),
+                operands,
+            });
+            address += 1 + mnemonic.arity;
+            continue;
+        }
+
+        let start = address;
+        let mut words = Vec::new();
+        while address < memory.len()
+            && !instructions.contains_key(&address)
+            && words.len() < DATA_PER_LINE
+            && (address == start || !labels.contains_key(&address))
+        {
+            words.push(memory[address]);
+            address += 1;
+        }
+        items.push(Item::Data {
+            address: start,
+            words,
+        });
+    }
+
+    Disassembly { items, labels }
+}
+
+/// render an operand in assembler syntax
+pub fn render_operand(mode: Mode, value: Word) -> String {
+    match mode {
+        Mode::Immediate => value.to_string(),
+        Mode::Position => format!("[{}]", value),
+        Mode::Relative if value < 0 => format!("[{}{}]", REGISTER, value),
+        Mode::Relative if value > 0 => format!("[{}+{}]", REGISTER, value),
+        Mode::Relative => format!("[{}]", REGISTER),
+    }
+}
+
+/// render an instruction in assembler syntax, naming jump targets which have labels
+pub(super) fn render_instruction(
+    mnemonic: &Mnemonic,
+    modes: &[Mode],
+    operands: &[Word],
+    labels: &BTreeMap<usize, String>,
+) -> String {
+    let rendered = modes
+        .iter()
+        .zip(operands)
+        .enumerate()
+        .map(|(idx, (&mode, &value))| {
+            let is_jump_target = idx == 1 && matches!(mnemonic.flow, Flow::JumpIf(_));
+            match labels.get(&(value as usize)) {
+                Some(label) if is_jump_target && mode == Mode::Immediate && value >= 0 => {
+                    label.clone()
+                }
+                _ => render_operand(mode, value),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{:<4} {}", mnemonic.name, rendered)
+        .trim_end()
+        .to_string()
+}
+
+/// render the instruction at `address` in assembler syntax, if there is a valid one there
+pub fn render_at<W>(memory: &[Word], address: usize, isa: &InstructionSet<W>) -> Option<String> {
+    let (mnemonic, modes) = decode(memory, address, isa)?;
+    let operands = &memory[address + 1..=address + mnemonic.arity];
+    Some(render_instruction(
+        &mnemonic,
+        &modes,
+        operands,
+        &BTreeMap::new(),
+    ))
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for item in &self.items {
+            if let Some(label) = self.labels.get(&item.address()) {
+                writeln!(f, "{}:", label)?;
+            }
+            match item {
+                Item::Instruction {
+                    address,
+                    mnemonic,
+                    modes,
+                    operands,
+                } => {
+                    let instruction = render_instruction(mnemonic, modes, operands, &self.labels);
+                    writeln!(f, "{:>6}  {}", address, instruction)?;
+                }
+                Item::Data { address, words } => {
+                    let rendered = words
+                        .iter()
+                        .map(Word::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    writeln!(f, "{:>6}  data {}", address, rendered)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    #[test]
+    fn test_follows_jumps() {
+        let memory = assemble(
+            "
+                    jt   1, start
+                    data 1, 2, 3
+            start:  in   [rb+7]
+                    jf   [rb+7], start
+                    out  -4
+                    hlt
+                    data 7
+            ",
+        )
+        .unwrap();
+        let listing = disassemble(&memory).to_string();
+        let expect = "     0  jt   1, L6
+     3  data 1, 2, 3
+L6:
+     6  in   [rb+7]
+     8  jf   [rb+7], L6
+    11  out  -4
+    13  hlt
+    14  data 7
+";
+        assert_eq!(listing, expect);
+    }
+
+    #[test]
+    fn test_unreachable_code_is_data() {
+        // the output instruction at 3 is never reached
+        let memory = vec![1105, 1, 5, 104, 0, 99];
+        let disassembly = disassemble(&memory);
+        assert_eq!(
+            disassembly.items[1],
+            Item::Data {
+                address: 3,
+                words: vec![104, 0],
+            }
+        );
+    }
+
+    #[test]
+    fn test_quine() {
+        let quine = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let disassembly = disassemble(&quine);
+        assert!(disassembly
+            .items
+            .iter()
+            .all(|item| matches!(item, Item::Instruction { .. })));
+    }
+}
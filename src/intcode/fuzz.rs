@@ -0,0 +1,579 @@
+//! Run random and mutated Intcode programs, looking for ways to break the machine.
+//!
+//! Each case is a program and some inputs, run under an instruction budget
+//! and a memory limit so that no case can hang or exhaust the host. The
+//! outcome of every run is classified: a clean halt, a wait for input, an
+//! exhausted budget, an `IntcodeError` (by variant), or a panic, which is
+//! always a bug. Programs are generated from the instruction set, biased
+//! towards the corner cases of `tick`: negative addresses, immediate-mode
+//! writes, relative base underflow, and extreme values. Once a corpus is
+//! given, most cases are mutations of its programs instead.
+//!
+//! The first failing case of each class is shrunk to a minimal reproducer,
+//! which still fails in the same way, and can be saved as comma-separated
+//! files.
+//!
+//! Panics are caught, but the panic hook still reports each one. The hook is
+//! global, so it's up to the caller whether to silence it during a run.
+
+use super::{InstructionSet, Intcode, IntcodeError, IntcodeMemory, State, Word};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+/// default quantity of instructions each case may retire
+pub const DEFAULT_BUDGET: u64 = 10_000;
+/// default quantity of memory cells each case may allocate
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 16;
+/// longest program the generator writes
+const MAX_LENGTH: usize = 24;
+/// most inputs the generator supplies
+const MAX_INPUTS: usize = 4;
+
+/// values on the edges of things
+const INTERESTING: &[Word] = &[
+    0,
+    1,
+    -1,
+    2,
+    99,
+    Word::MIN,
+    Word::MIN + 1,
+    Word::MAX,
+    Word::MAX - 1,
+    i32::MIN as Word,
+    i32::MAX as Word,
+    1 << 32,
+];
+
+/// A xorshift generator: not much, but deterministic for a given seed.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // the state must never be zero
+        Rng(seed ^ 0x9e37_79b9_7f4a_7c15 | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// a number in `0..bound`
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn choose<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+}
+
+/// A program and the inputs it is given.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Case {
+    pub memory: IntcodeMemory,
+    pub inputs: Vec<Word>,
+}
+
+fn comma_separated(words: &[Word]) -> String {
+    words
+        .iter()
+        .map(Word::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl Case {
+    /// write the program to `{stem}.txt` in `dir`, and any inputs to
+    /// `{stem}.inputs.txt`, returning the files written
+    pub fn save(&self, dir: &Path, stem: &str) -> io::Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(dir)?;
+        let mut written = Vec::new();
+        let program = dir.join(format!("{}.txt", stem));
+        std::fs::write(&program, comma_separated(&self.memory) + "\n")?;
+        written.push(program);
+        if !self.inputs.is_empty() {
+            let inputs = dir.join(format!("{}.inputs.txt", stem));
+            std::fs::write(&inputs, comma_separated(&self.inputs) + "\n")?;
+            written.push(inputs);
+        }
+        Ok(written)
+    }
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", comma_separated(&self.memory))?;
+        if !self.inputs.is_empty() {
+            write!(f, " <- {}", comma_separated(&self.inputs))?;
+        }
+        Ok(())
+    }
+}
+
+/// How a case ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Halted,
+    NeedsInput,
+    /// the instruction budget ran out
+    Exhausted,
+    Error(IntcodeError),
+    /// the machine panicked, with this message
+    Panic(String),
+}
+
+impl Verdict {
+    /// a name for the way the case ended, ignoring the details
+    pub fn class(&self) -> String {
+        match self {
+            Verdict::Halted => "halted".to_string(),
+            Verdict::NeedsInput => "needs-input".to_string(),
+            Verdict::Exhausted => "exhausted".to_string(),
+            Verdict::Error(error) => {
+                // the variant's name, from its debug representation
+                let debug = format!("{:?}", error);
+                let variant = debug.split(|c: char| !c.is_alphanumeric()).next();
+                format!("error-{}", variant.unwrap_or_default())
+            }
+            Verdict::Panic(_) => "panic".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Verdict::Halted => write!(f, "halted"),
+            Verdict::NeedsInput => write!(f, "needs input"),
+            Verdict::Exhausted => write!(f, "exhausted its budget"),
+            Verdict::Error(error) => write!(f, "error: {}", error),
+            Verdict::Panic(message) => write!(f, "panic: {}", message),
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "(unknown payload)".to_string(),
+        },
+    }
+}
+
+/// A failing case, and the smallest case found which fails the same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub verdict: Verdict,
+    pub original: Case,
+    pub minimal: Case,
+}
+
+/// What a fuzzing run found.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Report {
+    /// quantity of cases run, not counting those run while shrinking
+    pub runs: u64,
+    /// quantity of cases by class of verdict
+    pub classes: BTreeMap<String, u64>,
+    /// the first failure of each class, in the order found
+    pub findings: Vec<Finding>,
+}
+
+/// A generator and runner of cases.
+#[derive(Debug, Clone)]
+pub struct Fuzzer {
+    rng: Rng,
+    corpus: Vec<IntcodeMemory>,
+    instructions: InstructionSet,
+    budget: u64,
+    memory_limit: usize,
+    errors: bool,
+}
+
+impl Fuzzer {
+    pub fn new(seed: u64) -> Fuzzer {
+        Fuzzer {
+            rng: Rng::new(seed),
+            corpus: Vec::new(),
+            instructions: InstructionSet::standard(),
+            budget: DEFAULT_BUDGET,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            errors: false,
+        }
+    }
+
+    /// mutate these programs, as well as generating new ones
+    pub fn with_corpus(mut self, corpus: Vec<IntcodeMemory>) -> Self {
+        self.corpus.extend(corpus);
+        self
+    }
+
+    /// run and generate programs for this instruction set instead of the standard one
+    pub fn with_instruction_set(mut self, instructions: InstructionSet) -> Self {
+        self.instructions = instructions;
+        self
+    }
+
+    /// allow each case to retire at most `budget` instructions
+    pub fn with_budget(mut self, budget: u64) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// allow each case to allocate at most `limit` memory cells
+    pub fn with_memory_limit(mut self, limit: usize) -> Self {
+        self.memory_limit = limit;
+        self
+    }
+
+    /// count an `IntcodeError` as a failure, as well as a panic
+    pub fn with_errors(mut self, errors: bool) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    fn is_failure(&self, verdict: &Verdict) -> bool {
+        match verdict {
+            Verdict::Panic(_) => true,
+            Verdict::Error(_) => self.errors,
+            _ => false,
+        }
+    }
+
+    /// run a case to its end
+    ///
+    /// Panics are caught and reported as a verdict.
+    pub fn execute(&self, case: &Case) -> Verdict {
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut computer = Intcode::new(case.memory.clone())
+                .with_instruction_set(self.instructions.clone())
+                .with_budget(self.budget)
+                .with_memory_limit(self.memory_limit);
+            for &input in &case.inputs {
+                computer.push_input(input);
+            }
+            loop {
+                match computer.resume() {
+                    Ok(State::Output(_)) => {}
+                    Ok(State::NeedsInput) => return Verdict::NeedsInput,
+                    Ok(State::Halted) => return Verdict::Halted,
+                    Err(IntcodeError::BudgetExhausted { .. }) => return Verdict::Exhausted,
+                    Err(error) => return Verdict::Error(error),
+                }
+            }
+        }));
+        outcome.unwrap_or_else(|payload| Verdict::Panic(panic_message(payload)))
+    }
+
+    /// a value to use in a program or as an input
+    fn value(&mut self, length: usize) -> Word {
+        match self.rng.below(4) {
+            // near the program, including just below address 0
+            0 | 1 => self.rng.below(length + 8) as Word - 4,
+            2 => self.rng.choose(INTERESTING),
+            _ => self.rng.next() as Word,
+        }
+    }
+
+    /// an instruction word, mostly with valid modes
+    fn instruction(&mut self) -> Word {
+        let opcodes: Vec<Word> = self
+            .instructions
+            .mnemonics()
+            .map(|mnemonic| mnemonic.opcode as Word)
+            .collect();
+        let mut word = if opcodes.is_empty() {
+            self.rng.below(100) as Word
+        } else {
+            self.rng.choose(&opcodes)
+        };
+        for place in &[100, 1000, 10000] {
+            let mode = match self.rng.below(10) {
+                0 => self.rng.below(10),
+                _ => self.rng.below(3),
+            };
+            word += mode as Word * place;
+        }
+        if self.rng.below(20) == 0 {
+            word = -word;
+        }
+        word
+    }
+
+    fn word(&mut self, length: usize) -> Word {
+        match self.rng.below(2) {
+            0 => self.instruction(),
+            _ => self.value(length),
+        }
+    }
+
+    fn generate(&mut self) -> IntcodeMemory {
+        let length = 1 + self.rng.below(MAX_LENGTH);
+        (0..length).map(|_| self.word(length)).collect()
+    }
+
+    fn mutate(&mut self, mut memory: IntcodeMemory) -> IntcodeMemory {
+        for _ in 0..=self.rng.below(4) {
+            if memory.is_empty() {
+                memory.push(self.instruction());
+                continue;
+            }
+            let length = memory.len();
+            let idx = self.rng.below(length);
+            match self.rng.below(6) {
+                0 => memory[idx] = self.word(length),
+                1 => {
+                    let word = self.word(length);
+                    memory.insert(idx, word);
+                }
+                2 => {
+                    memory.remove(idx);
+                }
+                3 => {
+                    // change one parameter mode
+                    let place = self.rng.choose(&[100, 1000, 10000]);
+                    let mode = memory[idx] / place % 10;
+                    let replacement = self.rng.below(3) as Word;
+                    memory[idx] = memory[idx].wrapping_add((replacement - mode) * place);
+                }
+                4 => memory[idx] = self.rng.choose(INTERESTING),
+                _ => {
+                    let end = (idx + 1 + self.rng.below(4)).min(length);
+                    let chunk = memory[idx..end].to_vec();
+                    memory.splice(idx..idx, chunk);
+                }
+            }
+        }
+        memory
+    }
+
+    /// a new case: a mutation of the corpus, or a fresh program
+    pub fn case(&mut self) -> Case {
+        let memory = if !self.corpus.is_empty() && self.rng.below(4) != 0 {
+            let idx = self.rng.below(self.corpus.len());
+            let memory = self.corpus[idx].clone();
+            self.mutate(memory)
+        } else {
+            self.generate()
+        };
+        let inputs = (0..self.rng.below(MAX_INPUTS + 1))
+            .map(|_| self.value(memory.len()))
+            .collect();
+        Case { memory, inputs }
+    }
+
+    /// smaller variants of a case, most drastic first
+    fn simplifications(case: &Case) -> Vec<Case> {
+        let mut candidates = Vec::new();
+        let mut chunk = case.memory.len() / 2;
+        while chunk > 0 {
+            for start in (0..=case.memory.len() - chunk).step_by(chunk) {
+                let mut memory = case.memory.clone();
+                memory.drain(start..start + chunk);
+                candidates.push(Case {
+                    memory,
+                    inputs: case.inputs.clone(),
+                });
+            }
+            chunk /= 2;
+        }
+        for idx in 0..case.inputs.len() {
+            let mut inputs = case.inputs.clone();
+            inputs.remove(idx);
+            candidates.push(Case {
+                memory: case.memory.clone(),
+                inputs,
+            });
+        }
+        // bring values closer to zero
+        let smaller = |word: Word| vec![0, word / 2];
+        for idx in 0..case.memory.len() {
+            for word in smaller(case.memory[idx]) {
+                if word != case.memory[idx] {
+                    let mut memory = case.memory.clone();
+                    memory[idx] = word;
+                    candidates.push(Case {
+                        memory,
+                        inputs: case.inputs.clone(),
+                    });
+                }
+            }
+        }
+        for idx in 0..case.inputs.len() {
+            for word in smaller(case.inputs[idx]) {
+                if word != case.inputs[idx] {
+                    let mut inputs = case.inputs.clone();
+                    inputs[idx] = word;
+                    candidates.push(Case {
+                        memory: case.memory.clone(),
+                        inputs,
+                    });
+                }
+            }
+        }
+        candidates
+    }
+
+    /// the smallest variant of `case` found which ends in the same class of verdict
+    ///
+    /// Every simplification shortens the case or brings a value closer to
+    /// zero, so this always terminates.
+    pub fn shrink(&self, case: &Case) -> Case {
+        let class = self.execute(case).class();
+        let mut best = case.clone();
+        while let Some(smaller) = Fuzzer::simplifications(&best)
+            .into_iter()
+            .find(|candidate| self.execute(candidate).class() == class)
+        {
+            best = smaller;
+        }
+        best
+    }
+
+    /// run `iterations` cases, shrinking the first failure of each class
+    ///
+    /// The original of each failure joins the corpus, to be mutated further.
+    pub fn run(&mut self, iterations: u64) -> Report {
+        let mut report = Report::default();
+        for _ in 0..iterations {
+            let case = self.case();
+            let verdict = self.execute(&case);
+            report.runs += 1;
+            let class = verdict.class();
+            let count = report.classes.entry(class.clone()).or_insert(0);
+            *count += 1;
+            if *count > 1 || !self.is_failure(&verdict) {
+                continue;
+            }
+            let minimal = self.shrink(&case);
+            self.corpus.push(case.memory.clone());
+            report.findings.push(Finding {
+                verdict,
+                original: case,
+                minimal,
+            });
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::isa::{Effect, Flow, Mnemonic};
+
+    /// the standard instructions, and one which panics if its operand is negative
+    fn fragile() -> InstructionSet {
+        InstructionSet::standard().with(
+            Mnemonic {
+                name: "boom",
+                opcode: 42,
+                arity: 1,
+                writes: None,
+                flow: Flow::Next,
+            },
+            |p, _| {
+                assert!(p[0] >= 0, "boom");
+                Ok(Effect::Next)
+            },
+        )
+    }
+
+    fn case(memory: IntcodeMemory, inputs: &[Word]) -> Case {
+        Case {
+            memory,
+            inputs: inputs.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_verdicts() {
+        let fuzzer = Fuzzer::new(0).with_budget(100);
+        let verdict = |memory: IntcodeMemory| fuzzer.execute(&case(memory, &[])).class();
+        assert_eq!(verdict(vec![104, 1, 99]), "halted");
+        assert_eq!(verdict(vec![3, 0, 99]), "needs-input");
+        assert_eq!(verdict(vec![1105, 1, 0]), "exhausted");
+        assert_eq!(verdict(vec![11101, 1, 1, 0, 99]), "error-ImmediateWrite");
+        // relative base underflow
+        assert_eq!(verdict(vec![109, -5, 204, 0, 99]), "error-NegativeAddress");
+        assert_eq!(verdict(vec![1101, 1, 1, 1 << 40, 99]), "error-MemoryLimit");
+    }
+
+    #[test]
+    fn test_shrink() {
+        let fuzzer = Fuzzer::new(0).with_instruction_set(fragile());
+        let original = case(vec![1101, 2, 3, 20, 104, 5, 142, -7, 99], &[4, 5]);
+        assert!(matches!(fuzzer.execute(&original), Verdict::Panic(message) if message == "boom"));
+        assert_eq!(fuzzer.shrink(&original), case(vec![142, -1], &[]));
+
+        // errors shrink too
+        let original = case(vec![1101, 2, 3, 20, 109, -8, 21101, 1, 1, 0, 99], &[]);
+        assert_eq!(fuzzer.shrink(&original), case(vec![1, -1], &[]));
+    }
+
+    #[test]
+    fn test_run() {
+        let mut fuzzer = Fuzzer::new(2019)
+            .with_instruction_set(fragile())
+            .with_corpus(vec![vec![1101, 2, 3, 20, 104, 5, 42, 6, 99]])
+            .with_errors(true);
+        let report = fuzzer.run(2000);
+        assert_eq!(report.runs, 2000);
+        assert_eq!(report.classes.values().sum::<u64>(), 2000);
+        assert_eq!(
+            report.findings.len(),
+            report
+                .classes
+                .keys()
+                .filter(|class| class.starts_with("error-") || *class == "panic")
+                .count()
+        );
+        let panic = report
+            .findings
+            .iter()
+            .find(|finding| finding.verdict.class() == "panic")
+            .expect("no panic found");
+        assert!(panic.minimal.memory.len() <= panic.original.memory.len());
+        assert_eq!(panic.minimal.memory[0] % 100, 42);
+        for finding in &report.findings {
+            assert_eq!(
+                fuzzer.execute(&finding.minimal).class(),
+                finding.verdict.class()
+            );
+        }
+
+        // the same seed finds the same things
+        let again = Fuzzer::new(2019)
+            .with_instruction_set(fragile())
+            .with_corpus(vec![vec![1101, 2, 3, 20, 104, 5, 42, 6, 99]])
+            .with_errors(true)
+            .run(2000);
+        assert_eq!(again, report);
+    }
+
+    #[test]
+    fn test_save() {
+        let dir = std::env::temp_dir().join(format!("aoc2019-fuzz-{}", std::process::id()));
+        let written = case(vec![109, -1, 204, 0], &[3, -4])
+            .save(&dir, "error-NegativeAddress")
+            .unwrap();
+        assert_eq!(written.len(), 2);
+        assert_eq!(
+            std::fs::read_to_string(&written[0]).unwrap(),
+            "109,-1,204,0\n"
+        );
+        assert_eq!(std::fs::read_to_string(&written[1]).unwrap(), "3,-4\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
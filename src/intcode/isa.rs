@@ -0,0 +1,384 @@
+//! Instruction sets: which opcodes an Intcode machine understands, and what they do.
+//!
+//! An `InstructionSet` maps each opcode to a `Mnemonic`, describing the shape
+//! of the instruction, and a handler, describing its behavior. The machine,
+//! the assembler, the disassembler and the tracer all consult the same table,
+//! so a dialect which adds or replaces opcodes is understood by all of them.
+//!
+//! Handlers don't touch the machine directly. They receive the values of the
+//! instruction's read parameters, in order, and return an `Effect` for the
+//! machine to apply. The standard 2019 set is the default.
+
+use super::{Arithmetic, IntcodeWord, Opcode, Word};
+use std::fmt;
+use std::sync::Arc;
+
+/// quantity of distinct opcodes which an instruction word can encode
+const OPCODES: usize = 100;
+
+/// most parameters an instruction word has mode digits for
+const MAX_ARITY: usize = 3;
+
+/// How an instruction affects control flow, as far as static analysis is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// always continues with the following instruction
+    Next,
+    /// jumps to its second parameter if its first is nonzero (`true`)
+    /// or zero (`false`), and otherwise continues with the following instruction
+    JumpIf(bool),
+    /// never continues
+    Halt,
+}
+
+/// A mnemonic and the shape of the instruction it names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mnemonic {
+    pub name: &'static str,
+    pub opcode: Opcode,
+    pub arity: usize,
+    /// zero-based index of the parameter which is written to, if any
+    pub writes: Option<usize>,
+    pub flow: Flow,
+}
+
+/// The instructions of the standard 2019 set.
+pub const STANDARD: &[Mnemonic] = &[
+    Mnemonic {
+        name: "add",
+        opcode: 1,
+        arity: 3,
+        writes: Some(2),
+        flow: Flow::Next,
+    },
+    Mnemonic {
+        name: "mul",
+        opcode: 2,
+        arity: 3,
+        writes: Some(2),
+        flow: Flow::Next,
+    },
+    Mnemonic {
+        name: "in",
+        opcode: 3,
+        arity: 1,
+        writes: Some(0),
+        flow: Flow::Next,
+    },
+    Mnemonic {
+        name: "out",
+        opcode: 4,
+        arity: 1,
+        writes: None,
+        flow: Flow::Next,
+    },
+    Mnemonic {
+        name: "jt",
+        opcode: 5,
+        arity: 2,
+        writes: None,
+        flow: Flow::JumpIf(true),
+    },
+    Mnemonic {
+        name: "jf",
+        opcode: 6,
+        arity: 2,
+        writes: None,
+        flow: Flow::JumpIf(false),
+    },
+    Mnemonic {
+        name: "lt",
+        opcode: 7,
+        arity: 3,
+        writes: Some(2),
+        flow: Flow::Next,
+    },
+    Mnemonic {
+        name: "eq",
+        opcode: 8,
+        arity: 3,
+        writes: Some(2),
+        flow: Flow::Next,
+    },
+    Mnemonic {
+        name: "arb",
+        opcode: 9,
+        arity: 1,
+        writes: None,
+        flow: Flow::Next,
+    },
+    Mnemonic {
+        name: "hlt",
+        opcode: 99,
+        arity: 0,
+        writes: None,
+        flow: Flow::Halt,
+    },
+];
+
+/// What the machine should do once an instruction's handler has run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Effect<W = Word> {
+    /// continue with the following instruction
+    Next,
+    /// store the value in the written parameter, then continue
+    Store(W),
+    /// continue at the given address
+    Jump(W),
+    /// read an input into the written parameter, then continue
+    Input,
+    /// emit the value, then continue
+    Output(W),
+    /// add the value to the relative base, then continue
+    AdjustRelativeBase(W),
+    Halt,
+}
+
+/// Why a handler couldn't complete its instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// reported as `IntcodeError::Overflow`
+    Overflow,
+    /// reported as `IntcodeError::HandlerFailed`
+    Other(String),
+}
+
+/// What a handler returns.
+pub type Outcome<W = Word> = Result<Effect<W>, Fault>;
+
+/// The behavior of an instruction, given the values of its read parameters.
+pub type Handler<W = Word> = Arc<dyn Fn(&[W], Arithmetic) -> Outcome<W> + Send + Sync>;
+
+/// A table of the instructions a machine understands, indexed by opcode.
+#[derive(Clone)]
+pub struct InstructionSet<W = Word> {
+    instructions: Vec<Option<(Mnemonic, Handler<W>)>>,
+}
+
+impl<W> InstructionSet<W> {
+    /// an instruction set with no instructions at all
+    pub fn empty() -> Self {
+        InstructionSet {
+            instructions: vec![None; OPCODES],
+        }
+    }
+
+    /// add an instruction, replacing any which already used its opcode
+    ///
+    /// # Panics
+    ///
+    /// If the mnemonic can't be encoded in an instruction word, or its name is
+    /// already used by an instruction with a different opcode.
+    pub fn with<F>(mut self, mnemonic: Mnemonic, handler: F) -> Self
+    where
+        F: Fn(&[W], Arithmetic) -> Outcome<W> + Send + Sync + 'static,
+    {
+        let opcode = mnemonic.opcode as usize;
+        assert!(opcode < OPCODES, "opcode {} is out of range", opcode);
+        assert!(
+            mnemonic.arity <= MAX_ARITY,
+            "{} has more than {} parameters",
+            mnemonic.name,
+            MAX_ARITY
+        );
+        assert!(
+            mnemonic.writes.is_none_or(|idx| idx < mnemonic.arity),
+            "{} writes to a parameter it doesn't have",
+            mnemonic.name
+        );
+        if let Some(existing) = self.by_name(mnemonic.name) {
+            assert_eq!(
+                existing.opcode, mnemonic.opcode,
+                "{} is already opcode {}",
+                mnemonic.name, existing.opcode
+            );
+        }
+        self.instructions[opcode] = Some((mnemonic, Arc::new(handler)));
+        self
+    }
+
+    /// remove the instruction with `opcode`, if there is one
+    pub fn without(mut self, opcode: Opcode) -> Self {
+        if let Some(slot) = self.instructions.get_mut(opcode as usize) {
+            *slot = None;
+        }
+        self
+    }
+
+    /// the instruction with `opcode`, if there is one
+    pub fn get(&self, opcode: Opcode) -> Option<(&Mnemonic, &Handler<W>)> {
+        self.instructions
+            .get(opcode as usize)?
+            .as_ref()
+            .map(|(mnemonic, handler)| (mnemonic, handler))
+    }
+
+    pub fn by_opcode(&self, opcode: Opcode) -> Option<&Mnemonic> {
+        self.get(opcode).map(|(mnemonic, _)| mnemonic)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&Mnemonic> {
+        self.mnemonics()
+            .find(|mnemonic| mnemonic.name.eq_ignore_ascii_case(name))
+    }
+
+    /// every instruction in the set, in opcode order
+    pub fn mnemonics(&self) -> impl '_ + Iterator<Item = &Mnemonic> {
+        self.instructions
+            .iter()
+            .filter_map(|slot| slot.as_ref().map(|(mnemonic, _)| mnemonic))
+    }
+}
+
+impl<W: IntcodeWord> InstructionSet<W> {
+    /// the instructions of the 2019 puzzles
+    pub fn standard() -> Self {
+        let handlers: [Builtin<W>; 10] = [
+            |p, arithmetic| {
+                let sum = arithmetic.add(&p[0], &p[1]).ok_or(Fault::Overflow)?;
+                Ok(Effect::Store(sum))
+            },
+            |p, arithmetic| {
+                let product = arithmetic.mul(&p[0], &p[1]).ok_or(Fault::Overflow)?;
+                Ok(Effect::Store(product))
+            },
+            |_, _| Ok(Effect::Input),
+            |p, _| Ok(Effect::Output(p[0].clone())),
+            |p, _| Ok(jump_if(!p[0].is_zero(), &p[1])),
+            |p, _| Ok(jump_if(p[0].is_zero(), &p[1])),
+            |p, _| Ok(Effect::Store(((p[0] < p[1]) as Word).into())),
+            |p, _| Ok(Effect::Store(((p[0] == p[1]) as Word).into())),
+            |p, _| Ok(Effect::AdjustRelativeBase(p[0].clone())),
+            |_, _| Ok(Effect::Halt),
+        ];
+        STANDARD
+            .iter()
+            .zip(handlers.iter())
+            .fold(InstructionSet::empty(), |set, (&mnemonic, &handler)| {
+                set.with(mnemonic, handler)
+            })
+    }
+}
+
+/// the handler of a standard instruction, which needs no state
+type Builtin<W> = fn(&[W], Arithmetic) -> Outcome<W>;
+
+fn jump_if<W: Clone>(condition: bool, target: &W) -> Effect<W> {
+    if condition {
+        Effect::Jump(target.clone())
+    } else {
+        Effect::Next
+    }
+}
+
+impl<W: IntcodeWord> Default for InstructionSet<W> {
+    fn default() -> Self {
+        InstructionSet::standard()
+    }
+}
+
+impl<W> fmt::Debug for InstructionSet<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.mnemonics()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{asm::assemble_with, disasm::disassemble_with, Intcode, IntcodeError};
+    use std::sync::Mutex;
+
+    /// `dbg x`: report a value to the host without producing output
+    const DBG: Mnemonic = Mnemonic {
+        name: "dbg",
+        opcode: 50,
+        arity: 1,
+        writes: None,
+        flow: Flow::Next,
+    };
+
+    /// `neg x, y`: store the negation of x in y
+    const NEG: Mnemonic = Mnemonic {
+        name: "neg",
+        opcode: 51,
+        arity: 2,
+        writes: Some(1),
+        flow: Flow::Next,
+    };
+
+    #[test]
+    fn test_custom_dialect() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let isa = InstructionSet::standard()
+            .with(DBG, move |p: &[Word], _| {
+                log.lock().unwrap().push(p[0]);
+                Ok(Effect::Next)
+            })
+            .with(NEG, |p, _| Ok(Effect::Store(-p[0])));
+
+        let source = "
+                    neg  7, [x]
+                    dbg  [x]
+                    out  [x]
+                    hlt
+            x:      data 0
+            ";
+        let program = assemble_with(source, &isa).unwrap();
+        assert_eq!(program, vec![151, 7, 8, 50, 8, 4, 8, 99, 0]);
+        assert_eq!(
+            disassemble_with(&program, &[0], &isa).to_string(),
+            "     0  neg  7, [8]\n     3  dbg  [8]\n     5  out  [8]\n     7  hlt\n     8  data 0\n",
+        );
+
+        let mut computer = Intcode::new(program.clone()).with_instruction_set(isa);
+        assert_eq!(computer.run_collect(), Ok(vec![-7]));
+        assert_eq!(*seen.lock().unwrap(), vec![-7]);
+
+        // the standard set doesn't know these opcodes
+        assert!(assemble_with(source, &InstructionSet::<Word>::standard()).is_err());
+        assert_eq!(
+            Intcode::new(program).run(),
+            Err(IntcodeError::InvalidOpcode { ip: 0, word: 151 })
+        );
+    }
+
+    #[test]
+    fn test_replaced_and_failing_instructions() {
+        // a dialect whose `add` refuses negative operands
+        let isa = InstructionSet::standard().with(STANDARD[0], |p: &[Word], _| {
+            if p.iter().any(|&v| v < 0) {
+                return Err(Fault::Other("negative operand".to_string()));
+            }
+            Ok(Effect::Store(p[0] + p[1]))
+        });
+        let mut computer = Intcode::new(vec![1101, 2, 3, 0, 99]).with_instruction_set(isa.clone());
+        computer.run().unwrap();
+        assert_eq!(computer.memory()[0], 5);
+
+        let mut computer = Intcode::new(vec![1101, -2, 3, 0, 99]).with_instruction_set(isa);
+        assert_eq!(
+            computer.run(),
+            Err(IntcodeError::HandlerFailed {
+                ip: 0,
+                word: 1101,
+                message: "negative operand".to_string(),
+            })
+        );
+
+        let isa = InstructionSet::<Word>::standard().without(99);
+        assert!(isa.by_name("hlt").is_none());
+        assert_eq!(isa.mnemonics().count(), STANDARD.len() - 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "add is already opcode 1")]
+    fn test_duplicate_name() {
+        let mnemonic = Mnemonic {
+            opcode: 20,
+            ..STANDARD[0]
+        };
+        InstructionSet::<Word>::standard().with(mnemonic, |_, _| Ok(Effect::Next));
+    }
+}
@@ -0,0 +1,297 @@
+//! Find problems in an Intcode program without running it.
+//!
+//! Only code reachable from the entry points is checked, as the control-flow
+//! graph discovers it; anything else is presumed to be data. Relative-mode
+//! operands depend on the relative base at run time, so they aren't checked.
+
+use super::{
+    cfg::{control_flow_with, EdgeKind, Instruction},
+    isa::Flow,
+    InstructionSet, Intcode, IntcodeError, Mode, Word,
+};
+use std::collections::BTreeSet;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// the program may fail here, depending on its inputs
+    Warning,
+    /// the program fails if execution reaches here
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintKind {
+    /// execution reaches a word which doesn't destructure into an instruction
+    InvalidWord(IntcodeError),
+    /// execution reaches an instruction which the ISA doesn't define
+    UnknownOpcode(Word),
+    /// execution reaches an instruction whose parameters run past the end of the program
+    Truncated(Word),
+    /// execution reaches an instruction which writes to an immediate-mode parameter
+    ImmediateWrite(Word),
+    /// execution falls through past the end of the program
+    RunsOffEnd,
+    /// a jump to a constant address outside the program
+    JumpOutOfBounds(Word),
+    /// a position-mode parameter with a negative address
+    NegativeAddress(Word),
+    /// a read of a cell beyond the program which is never written
+    UninitializedRead(Word),
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use LintKind::*;
+        match self {
+            InvalidWord(IntcodeError::InvalidMode { word, .. }) => {
+                write!(f, "invalid parameter mode: {}", word)
+            }
+            InvalidWord(IntcodeError::InvalidOpcode { word, .. }) => {
+                write!(f, "invalid opcode: {}", word)
+            }
+            InvalidWord(err) => write!(f, "invalid instruction: {}", err),
+            UnknownOpcode(word) => write!(f, "unknown opcode: {}", word),
+            Truncated(word) => write!(f, "instruction runs past the end of the program: {}", word),
+            ImmediateWrite(word) => write!(f, "write to an immediate parameter: {}", word),
+            RunsOffEnd => write!(f, "execution runs past the end of the program"),
+            JumpOutOfBounds(target) => write!(f, "jump to {}, outside the program", target),
+            NegativeAddress(address) => write!(f, "negative address: {}", address),
+            UninitializedRead(address) => {
+                write!(f, "read of {}, which is never initialized", address)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// address of the offending instruction
+    pub address: usize,
+    pub severity: Severity,
+    pub kind: LintKind,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>6}  {}: {}", self.address, self.severity, self.kind)
+    }
+}
+
+/// explain why no instruction could be decoded at `address`
+fn diagnose(memory: &[Word], address: usize, isa: &InstructionSet) -> LintKind {
+    let word = memory[address];
+    let (opcode, p1, p2, p3) = match Intcode::destructure(word) {
+        Ok(destructured) => destructured,
+        Err(IntcodeError::InvalidMode { .. }) => {
+            return LintKind::InvalidWord(IntcodeError::InvalidMode { ip: address, word })
+        }
+        Err(_) => return LintKind::InvalidWord(IntcodeError::InvalidOpcode { ip: address, word }),
+    };
+    match isa.by_opcode(opcode) {
+        None => LintKind::UnknownOpcode(word),
+        Some(mnemonic) if address + mnemonic.arity >= memory.len() => LintKind::Truncated(word),
+        Some(mnemonic) => {
+            debug_assert_eq!(
+                mnemonic.writes.map(|idx| [p1, p2, p3][idx]),
+                Some(Mode::Immediate),
+            );
+            LintKind::ImmediateWrite(word)
+        }
+    }
+}
+
+/// whether a jump instruction can ever take its jump
+fn may_jump(instruction: &Instruction, jumps_when: bool) -> (bool, Severity) {
+    match instruction.modes[0] {
+        Mode::Immediate => (
+            (instruction.operands[0] != 0) == jumps_when,
+            Severity::Error,
+        ),
+        _ => (true, Severity::Warning),
+    }
+}
+
+/// Check a program, starting from address 0.
+pub fn lint(memory: &[Word]) -> Vec<Diagnostic> {
+    lint_with(memory, &[0], &InstructionSet::standard())
+}
+
+/// Check a program written for the instructions of `isa`, starting from each
+/// of the given entry points.
+pub fn lint_with(memory: &[Word], entry_points: &[usize], isa: &InstructionSet) -> Vec<Diagnostic> {
+    let cfg = control_flow_with(memory, entry_points, isa);
+    let mut diagnostics = Vec::new();
+    let mut diagnose_at = |address: usize, severity: Severity, kind: LintKind| {
+        diagnostics.push(Diagnostic {
+            address,
+            severity,
+            kind,
+        })
+    };
+
+    // addresses which execution reaches, but where no instruction could be decoded
+    let mut reached: BTreeSet<usize> = entry_points.iter().cloned().collect();
+    for block in cfg.blocks.values() {
+        let last = &block.instructions[block.instructions.len() - 1];
+        for edge in &block.successors {
+            match (edge.kind, edge.target) {
+                (EdgeKind::FallThrough, Some(target)) | (EdgeKind::ReturnSite, Some(target))
+                    if target >= memory.len() =>
+                {
+                    diagnose_at(last.address, Severity::Error, LintKind::RunsOffEnd)
+                }
+                (_, Some(target)) => {
+                    reached.insert(target);
+                }
+                _ => {}
+            }
+        }
+    }
+    for address in reached {
+        if address < memory.len() && !cfg.blocks.contains_key(&address) {
+            diagnose_at(address, Severity::Error, diagnose(memory, address, isa));
+        }
+    }
+
+    let instructions = || cfg.blocks.values().flat_map(|block| &block.instructions);
+    let written: BTreeSet<Word> = instructions()
+        .filter_map(|instruction| {
+            let idx = instruction.mnemonic.writes?;
+            match instruction.modes[idx] {
+                Mode::Position => Some(instruction.operands[idx]),
+                _ => None,
+            }
+        })
+        .collect();
+
+    for instruction in instructions() {
+        for (idx, (&mode, &value)) in instruction
+            .modes
+            .iter()
+            .zip(&instruction.operands)
+            .enumerate()
+        {
+            if mode != Mode::Position {
+                continue;
+            }
+            if value < 0 {
+                diagnose_at(
+                    instruction.address,
+                    Severity::Error,
+                    LintKind::NegativeAddress(value),
+                );
+            } else if Some(idx) != instruction.mnemonic.writes
+                && value as usize >= memory.len()
+                && !written.contains(&value)
+            {
+                diagnose_at(
+                    instruction.address,
+                    Severity::Warning,
+                    LintKind::UninitializedRead(value),
+                );
+            }
+        }
+
+        if let Flow::JumpIf(jumps_when) = instruction.mnemonic.flow {
+            let target = instruction.operands[1];
+            let (may_jump, severity) = may_jump(instruction, jumps_when);
+            let outside = target < 0 || target as usize >= memory.len();
+            if may_jump && instruction.modes[1] == Mode::Immediate && outside {
+                diagnose_at(
+                    instruction.address,
+                    severity,
+                    LintKind::JumpOutOfBounds(target),
+                );
+            }
+        }
+    }
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.address);
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    #[test]
+    fn test_clean() {
+        let memory = assemble(
+            "
+            loop:   in   [value]
+                    jf   [value], done
+                    out  [value]
+                    jt   1, loop
+            done:   hlt
+            value:  data 0
+            ",
+        )
+        .unwrap();
+        assert_eq!(lint(&memory), vec![]);
+    }
+
+    #[test]
+    fn test_problems() {
+        let memory = assemble(
+            "
+                    in   [100]
+                    jt   [100], bad
+                    add  [101], [102], [102]
+                    jf   [102], -5
+                    jt   1, 1000
+            bad:    data 11101, 1, 2, 3
+            ",
+        )
+        .unwrap();
+        let diagnostics: Vec<_> = lint(&memory).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                "     5  warning: read of 101, which is never initialized",
+                "     9  warning: jump to -5, outside the program",
+                "    12  error: jump to 1000, outside the program",
+                "    15  error: write to an immediate parameter: 11101",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_words() {
+        // each entry point is broken differently
+        let memory = vec![42, 30001, 1, 0, 0, 0, 104];
+        let kinds: Vec<_> = lint_with(&memory, &[0, 1, 2], &InstructionSet::standard())
+            .into_iter()
+            .map(|diagnostic| (diagnostic.address, diagnostic.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (0, LintKind::UnknownOpcode(42)),
+                (
+                    1,
+                    LintKind::InvalidWord(IntcodeError::InvalidMode { ip: 1, word: 30001 })
+                ),
+                (6, LintKind::Truncated(104)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_runs_off_end() {
+        let diagnostics = lint(&[104, 0]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, LintKind::RunsOffEnd);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+}
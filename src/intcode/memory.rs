@@ -0,0 +1,174 @@
+//! Storage backends for Intcode memory.
+//!
+//! Intcode programs may address any non-negative cell, and every cell they
+//! haven't written reads as 0. `IntcodeMemory`, a plain vector, stores every
+//! cell up to the highest address written, which is fastest for the compact
+//! address spaces most programs use. `PagedMemory` allocates fixed-size pages
+//! only when a nonzero value is written to them, so programs which scatter
+//! writes across a huge address space don't allocate all the space between.
+//!
+//! Both backends can hold any `IntcodeWord`.
+
+use super::{IntcodeWord, Word};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Storage for an Intcode machine's memory.
+pub trait Memory: fmt::Debug + Clone + Default {
+    type Word: IntcodeWord;
+
+    /// the value at `address`; cells which have never been written read as 0
+    fn read(&self, address: usize) -> Self::Word;
+
+    /// store `value` at `address`, allocating storage as required
+    fn write(&mut self, address: usize, value: Self::Word);
+
+    /// one past the highest address which has been loaded or written
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// forget every cell at or beyond `len`, so they read as 0 again
+    fn truncate(&mut self, len: usize);
+
+    /// the quantity of cells allocated if `value` were written to `address`
+    ///
+    /// This lets a memory limit be enforced before anything is allocated.
+    fn allocated_after(&self, address: usize, value: &Self::Word) -> usize;
+}
+
+impl<W: IntcodeWord> Memory for Vec<W> {
+    type Word = W;
+
+    fn read(&self, address: usize) -> W {
+        self.get(address).cloned().unwrap_or_default()
+    }
+
+    fn write(&mut self, address: usize, value: W) {
+        if address >= Vec::len(self) {
+            self.resize_with(address + 1, Default::default);
+        }
+        self[address] = value;
+    }
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len)
+    }
+
+    fn allocated_after(&self, address: usize, _value: &W) -> usize {
+        Vec::len(self).max(address + 1)
+    }
+}
+
+const PAGE_BITS: usize = 10;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+const OFFSET_MASK: usize = PAGE_SIZE - 1;
+
+/// Sparse memory, allocated a page at a time.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PagedMemory<W = Word> {
+    pages: BTreeMap<usize, Box<[W]>>,
+    len: usize,
+}
+
+impl<W> PagedMemory<W> {
+    /// the quantity of pages which have been allocated
+    pub fn pages(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+impl<W: IntcodeWord> From<Vec<W>> for PagedMemory<W> {
+    fn from(words: Vec<W>) -> PagedMemory<W> {
+        let mut memory = PagedMemory::default();
+        for (address, word) in words.into_iter().enumerate() {
+            memory.write(address, word);
+        }
+        memory
+    }
+}
+
+impl<W: IntcodeWord> Memory for PagedMemory<W> {
+    type Word = W;
+
+    fn read(&self, address: usize) -> W {
+        self.pages
+            .get(&(address >> PAGE_BITS))
+            .map(|page| page[address & OFFSET_MASK].clone())
+            .unwrap_or_default()
+    }
+
+    fn write(&mut self, address: usize, value: W) {
+        self.len = self.len.max(address + 1);
+        let page = address >> PAGE_BITS;
+        if value.is_zero() && !self.pages.contains_key(&page) {
+            // unmapped cells already read as 0
+            return;
+        }
+        self.pages
+            .entry(page)
+            .or_insert_with(|| vec![W::default(); PAGE_SIZE].into_boxed_slice())
+            [address & OFFSET_MASK] = value;
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        // the page containing `len` survives, less its cells from `len` on
+        let first_dropped = (len + OFFSET_MASK) >> PAGE_BITS;
+        self.pages.split_off(&first_dropped);
+        if let Some(page) = self.pages.get_mut(&(len >> PAGE_BITS)) {
+            for cell in page[len & OFFSET_MASK..].iter_mut() {
+                *cell = W::default();
+            }
+        }
+        self.len = len;
+    }
+
+    fn allocated_after(&self, address: usize, value: &W) -> usize {
+        let page = address >> PAGE_BITS;
+        let new_page = !value.is_zero() && !self.pages.contains_key(&page);
+        (self.pages.len() + new_page as usize) * PAGE_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paged_memory() {
+        let mut memory = PagedMemory::from(vec![1, 2, 3]);
+        assert_eq!(memory.len(), 3);
+        assert_eq!(memory.read(2), 3);
+        assert_eq!(memory.read(1 << 40), 0);
+
+        // writing zeros never allocates
+        memory.write(1 << 40, 0);
+        assert_eq!(memory.pages(), 1);
+        assert_eq!(memory.len(), (1 << 40) + 1);
+
+        assert_eq!(memory.allocated_after(1 << 40, &5), 2 * PAGE_SIZE);
+        memory.write(1 << 40, 5);
+        assert_eq!(memory.pages(), 2);
+        assert_eq!(memory.read(1 << 40), 5);
+
+        memory.truncate(2);
+        assert_eq!(memory.pages(), 1);
+        assert_eq!(memory.len(), 2);
+        assert_eq!(memory.read(1), 2);
+        assert_eq!(memory.read(2), 0);
+        assert_eq!(memory.read(1 << 40), 0);
+    }
+}
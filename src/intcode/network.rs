@@ -0,0 +1,418 @@
+//! Simulate a network of Intcode machines which exchange addressed packets.
+//!
+//! Each machine boots with its network address as its first input. It sends
+//! a packet by outputting three words: the destination address, then `x` and
+//! `y`. Packets for machines are queued at their destinations; when a machine
+//! wants input and its queue is empty, it receives `-1`.
+//!
+//! Packets for any other address go to the NAT, which is also notified of
+//! every packet sent, and of each time the network goes idle. The network is
+//! idle when no packets are queued and every running machine has polled its
+//! empty queue repeatedly without sending anything.
+//!
+//! Machines run in turn, in address order, in a single thread, so a
+//! simulation always runs the same way. A machine's turn lasts until it wants
+//! input a second time, or halts, so a machine which never reads input will
+//! never yield.
+
+use super::{Intcode, IntcodeError, IntcodeMemory, State, Word};
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Consecutive empty polls after which a silent machine counts as idle
+pub const IDLE_POLLS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Packet {
+    pub x: Word,
+    pub y: Word,
+}
+
+/// What the NAT wants the network to do next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Continue,
+    /// deliver a packet to the machine at this address; dropped if there is none
+    Send(usize, Packet),
+    /// end the simulation
+    Stop,
+}
+
+/// A node which handles packets sent to addresses without machines, and
+/// which can wake the network when it goes idle.
+pub trait Nat {
+    /// handle a packet sent to `address`, which is not a machine
+    fn receive(&mut self, address: Word, packet: Packet) -> Action;
+
+    /// the network is idle
+    ///
+    /// If this returns `Continue`, the simulation ends, as nothing else
+    /// will happen.
+    fn idle(&mut self) -> Action;
+
+    /// observe every packet a machine sends, including those sent to the NAT
+    fn observe(&mut self, _from: usize, _to: Word, _packet: Packet) {}
+}
+
+/// Packets sent to addresses without machines are dropped, and an idle
+/// network stays idle.
+impl Nat for () {
+    fn receive(&mut self, _: Word, _: Packet) -> Action {
+        Action::Continue
+    }
+
+    fn idle(&mut self) -> Action {
+        Action::Continue
+    }
+}
+
+/// Stop as soon as any packet arrives at the NAT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FirstPacket {
+    pub packet: Option<Packet>,
+}
+
+impl Nat for FirstPacket {
+    fn receive(&mut self, _: Word, packet: Packet) -> Action {
+        self.packet = Some(packet);
+        Action::Stop
+    }
+
+    fn idle(&mut self) -> Action {
+        Action::Continue
+    }
+}
+
+/// Remember the latest packet to arrive, and deliver it to address 0 whenever
+/// the network goes idle. Stop instead of delivering the same `y` twice in a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Relay {
+    latest: Option<Packet>,
+    delivered: Option<Word>,
+    /// the `y` value which would have been delivered twice in a row
+    pub repeated: Option<Word>,
+}
+
+impl Nat for Relay {
+    fn receive(&mut self, _: Word, packet: Packet) -> Action {
+        self.latest = Some(packet);
+        Action::Continue
+    }
+
+    fn idle(&mut self) -> Action {
+        let packet = match self.latest {
+            Some(packet) => packet,
+            None => return Action::Continue,
+        };
+        if self.delivered == Some(packet.y) {
+            self.repeated = Some(packet.y);
+            return Action::Stop;
+        }
+        self.delivered = Some(packet.y);
+        Action::Send(0, packet)
+    }
+}
+
+/// Why a simulation ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// the NAT stopped it
+    Stopped,
+    /// the network went idle and the NAT didn't wake it
+    Idle,
+    /// every machine halted
+    Halted,
+}
+
+/// A machine on the network failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkError {
+    pub address: usize,
+    pub error: IntcodeError,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "machine {}: {}", self.address, self.error)
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+#[derive(Debug, Clone)]
+struct Host {
+    computer: Intcode,
+    queue: VecDeque<Packet>,
+    /// words of a packet the machine is partway through sending
+    sending: Vec<Word>,
+    /// consecutive polls of an empty queue, without sending anything
+    empty_polls: usize,
+}
+
+impl Host {
+    fn is_idle(&self) -> bool {
+        self.computer.is_halted() || (self.queue.is_empty() && self.empty_polls >= IDLE_POLLS)
+    }
+}
+
+pub struct Network<N: Nat = ()> {
+    hosts: Vec<Host>,
+    nat: N,
+    sent: u64,
+}
+
+impl<N: Nat> Network<N> {
+    /// boot `size` copies of the program at addresses `0..size`
+    pub fn new(memory: &IntcodeMemory, size: usize, nat: N) -> Self {
+        let hosts = (0..size)
+            .map(|address| {
+                let mut computer = Intcode::new(memory.clone());
+                computer.push_input(address as Word);
+                Host {
+                    computer,
+                    queue: VecDeque::new(),
+                    sending: Vec::with_capacity(3),
+                    empty_polls: 0,
+                }
+            })
+            .collect();
+        Network {
+            hosts,
+            nat,
+            sent: 0,
+        }
+    }
+
+    pub fn nat(&self) -> &N {
+        &self.nat
+    }
+
+    pub fn computer(&self, address: usize) -> &Intcode {
+        &self.hosts[address].computer
+    }
+
+    /// quantity of packets sent so far, by machines and the NAT
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// the network is idle when nothing more can happen without the NAT
+    pub fn is_idle(&self) -> bool {
+        self.hosts.iter().all(Host::is_idle)
+    }
+
+    /// queue a packet for the machine at `address`, or hand it to the NAT
+    fn route(&mut self, from: usize, to: Word, packet: Packet) -> Action {
+        self.sent += 1;
+        self.nat.observe(from, to, packet);
+        if to >= 0 && (to as usize) < self.hosts.len() {
+            self.hosts[to as usize].queue.push_back(packet);
+            Action::Continue
+        } else {
+            self.nat.receive(to, packet)
+        }
+    }
+
+    /// act on the NAT's instructions; whether to stop
+    fn obey(&mut self, action: Action) -> bool {
+        match action {
+            Action::Continue => false,
+            Action::Send(address, packet) => {
+                if let Some(host) = self.hosts.get_mut(address) {
+                    self.sent += 1;
+                    host.queue.push_back(packet);
+                }
+                false
+            }
+            Action::Stop => true,
+        }
+    }
+
+    /// give the machine at `address` its turn; whether the NAT stopped the simulation
+    fn turn(&mut self, address: usize) -> Result<bool, NetworkError> {
+        let mut fed = false;
+        loop {
+            let host = &mut self.hosts[address];
+            let state = host
+                .computer
+                .resume()
+                .map_err(|error| NetworkError { address, error })?;
+            match state {
+                State::Output(value) => {
+                    host.empty_polls = 0;
+                    host.sending.push(value);
+                    if let [to, x, y] = host.sending[..] {
+                        host.sending.clear();
+                        let action = self.route(address, to, Packet { x, y });
+                        if self.obey(action) {
+                            return Ok(true);
+                        }
+                    }
+                }
+                State::NeedsInput if fed => return Ok(false),
+                State::NeedsInput => {
+                    fed = true;
+                    match host.queue.pop_front() {
+                        Some(Packet { x, y }) => {
+                            host.empty_polls = 0;
+                            host.computer.push_input(x);
+                            host.computer.push_input(y);
+                        }
+                        None => {
+                            host.empty_polls += 1;
+                            host.computer.push_input(-1);
+                        }
+                    }
+                }
+                State::Halted => return Ok(false),
+            }
+        }
+    }
+
+    /// give each machine one turn, then wake the network if it's idle
+    pub fn step(&mut self) -> Result<Option<Outcome>, NetworkError> {
+        for address in 0..self.hosts.len() {
+            if !self.hosts[address].computer.is_halted() && self.turn(address)? {
+                return Ok(Some(Outcome::Stopped));
+            }
+        }
+        if self.hosts.iter().all(|host| host.computer.is_halted()) {
+            return Ok(Some(Outcome::Halted));
+        }
+        if self.is_idle() {
+            let action = self.nat.idle();
+            if action == Action::Continue {
+                return Ok(Some(Outcome::Idle));
+            }
+            if self.obey(action) {
+                return Ok(Some(Outcome::Stopped));
+            }
+        }
+        Ok(None)
+    }
+
+    /// run until the NAT stops the simulation, the network idles, or every machine halts
+    pub fn run(&mut self) -> Result<Outcome, NetworkError> {
+        loop {
+            if let Some(outcome) = self.step()? {
+                return Ok(outcome);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    /// announce `(address, 10 * address)` to the NAT, then echo every packet received back to it
+    fn echo() -> IntcodeMemory {
+        assemble(
+            "
+                    in   [addr]
+                    mul  [addr], 10, [y]
+                    out  255
+                    out  [addr]
+                    out  [y]
+            loop:   in   [x]
+                    eq   [x], -1, [t]
+                    jt   [t], loop
+                    in   [y]
+                    out  255
+                    out  [x]
+                    out  [y]
+                    jt   1, loop
+            addr:   data 0
+            x:      data 0
+            y:      data 0
+            t:      data 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[derive(Default)]
+    struct Monitor {
+        seen: Vec<(usize, Word, Packet)>,
+    }
+
+    impl Nat for Monitor {
+        fn receive(&mut self, _: Word, _: Packet) -> Action {
+            Action::Continue
+        }
+
+        fn idle(&mut self) -> Action {
+            Action::Continue
+        }
+
+        fn observe(&mut self, from: usize, to: Word, packet: Packet) {
+            self.seen.push((from, to, packet));
+        }
+    }
+
+    #[test]
+    fn test_first_packet() {
+        let mut network = Network::new(&echo(), 3, FirstPacket::default());
+        assert_eq!(network.run(), Ok(Outcome::Stopped));
+        assert_eq!(network.nat().packet, Some(Packet { x: 0, y: 0 }));
+    }
+
+    #[test]
+    fn test_idle() {
+        let mut network = Network::new(&echo(), 3, Monitor::default());
+        assert_eq!(network.run(), Ok(Outcome::Idle));
+        assert_eq!(
+            network.nat().seen,
+            vec![
+                (0, 255, Packet { x: 0, y: 0 }),
+                (1, 255, Packet { x: 1, y: 10 }),
+                (2, 255, Packet { x: 2, y: 20 }),
+            ]
+        );
+        assert!(network.is_idle());
+    }
+
+    #[test]
+    fn test_relay() {
+        let mut network = Network::new(&echo(), 3, Relay::default());
+        assert_eq!(network.run(), Ok(Outcome::Stopped));
+        assert_eq!(network.nat().repeated, Some(20));
+        // three announcements, one delivery, and its echo
+        assert_eq!(network.sent(), 5);
+    }
+
+    #[test]
+    fn test_send_to_missing_host() {
+        /// when idle, send a packet to a machine which doesn't exist, once
+        #[derive(Default)]
+        struct Stray(bool);
+
+        impl Nat for Stray {
+            fn receive(&mut self, _: Word, _: Packet) -> Action {
+                Action::Continue
+            }
+
+            fn idle(&mut self) -> Action {
+                if std::mem::replace(&mut self.0, true) {
+                    Action::Continue
+                } else {
+                    Action::Send(7, Packet { x: 1, y: 2 })
+                }
+            }
+        }
+
+        let mut network = Network::new(&echo(), 2, Stray::default());
+        assert_eq!(network.run(), Ok(Outcome::Idle));
+        assert!(network.nat().0);
+        // only the announcements were delivered
+        assert_eq!(network.sent(), 2);
+    }
+
+    #[test]
+    fn test_halted() {
+        let memory = assemble("in [0]\nhlt").unwrap();
+        let mut network = Network::new(&memory, 2, ());
+        assert_eq!(network.run(), Ok(Outcome::Halted));
+    }
+}
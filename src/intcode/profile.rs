@@ -0,0 +1,225 @@
+//! Count the instructions an Intcode machine executes.
+//!
+//! A profile records how many times the instruction at each address retired,
+//! and how many instructions of each opcode retired. Since programs may modify
+//! their own code, the opcode counts are taken as instructions execute rather
+//! than derived from the final memory.
+
+use super::{
+    disasm::{disassemble_with, render_at},
+    InstructionSet, Intcode, Memory, Opcode, Word,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Profile {
+    counts: Vec<u64>,
+    opcodes: BTreeMap<Opcode, u64>,
+}
+
+impl Profile {
+    pub(super) fn record(&mut self, ip: usize, opcode: Opcode) {
+        if ip >= self.counts.len() {
+            self.counts.resize(ip + 1, 0);
+        }
+        self.counts[ip] += 1;
+        *self.opcodes.entry(opcode).or_default() += 1;
+    }
+
+    /// how many times the instruction at `address` retired
+    pub fn count(&self, address: usize) -> u64 {
+        self.counts.get(address).cloned().unwrap_or_default()
+    }
+
+    /// total quantity of instructions retired
+    pub fn retired(&self) -> u64 {
+        self.opcodes.values().sum()
+    }
+
+    /// quantity of instructions retired, by opcode
+    pub fn per_opcode(&self) -> &BTreeMap<Opcode, u64> {
+        &self.opcodes
+    }
+
+    /// addresses of every instruction which retired at least once
+    pub fn executed(&self) -> impl '_ + Iterator<Item = usize> {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(address, _)| address)
+    }
+
+    /// the `n` most-executed addresses and their counts, hottest first
+    pub fn hot(&self, n: usize) -> Vec<(usize, u64)> {
+        let mut hot: Vec<_> = self
+            .executed()
+            .map(|address| (address, self.counts[address]))
+            .collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot.truncate(n);
+        hot
+    }
+
+    /// for each cell of `memory`, whether it was part of an executed instruction
+    ///
+    /// Instruction lengths are decoded from `memory` using `isa`, so for
+    /// self-modifying programs this should be the memory as it was when the
+    /// code executed.
+    pub fn coverage<W>(&self, memory: &[Word], isa: &InstructionSet<W>) -> Vec<bool> {
+        let mut covered = vec![false; memory.len()];
+        for address in self.executed() {
+            let arity = Intcode::destructure(memory.get(address).cloned().unwrap_or_default())
+                .ok()
+                .and_then(|(opcode, _, _, _)| isa.by_opcode(opcode))
+                .map_or(0, |mnemonic| mnemonic.arity);
+            let end = (address + arity + 1).min(memory.len());
+            for cell in covered.iter_mut().take(end).skip(address) {
+                *cell = true;
+            }
+        }
+        covered
+    }
+
+    /// summarize this profile of a run of `memory` with the instructions of
+    /// `isa`, listing the `top` hottest instructions
+    pub fn report<'a, W>(
+        &'a self,
+        memory: &'a [Word],
+        isa: &'a InstructionSet<W>,
+        top: usize,
+    ) -> Report<'a, W> {
+        Report {
+            profile: self,
+            memory,
+            isa,
+            top,
+        }
+    }
+}
+
+/// A human-readable profile summary, including a listing annotated with
+/// execution counts.
+///
+/// Never-executed instructions are marked with `-`. The listing is
+/// disassembled from every executed address, so code which is only reached
+/// through dynamic jumps still appears as instructions.
+pub struct Report<'a, W = Word> {
+    profile: &'a Profile,
+    memory: &'a [Word],
+    isa: &'a InstructionSet<W>,
+    top: usize,
+}
+
+impl<'a, W> fmt::Display for Report<'a, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let profile = self.profile;
+        writeln!(f, "instructions retired: {}", profile.retired())?;
+
+        writeln!(f, "\nper opcode:")?;
+        for (&opcode, count) in profile.per_opcode() {
+            let name = self
+                .isa
+                .by_opcode(opcode)
+                .map_or("???", |mnemonic| mnemonic.name);
+            writeln!(f, "  {:<4} {:>12}", name, count)?;
+        }
+
+        let memory = self.memory.to_vec();
+        let mut entry_points: Vec<usize> = profile.executed().collect();
+        entry_points.insert(0, 0);
+        let disassembly = disassemble_with(&memory, &entry_points, self.isa);
+
+        writeln!(f, "\nhot instructions:")?;
+        for (address, count) in profile.hot(self.top) {
+            let instruction = render_at(self.memory, address, self.isa).unwrap_or_default();
+            writeln!(f, "  {:>12}  {:>6}  {}", count, address, instruction)?;
+        }
+
+        let covered = profile.coverage(self.memory, self.isa);
+        let quantity = covered.iter().filter(|&&covered| covered).count();
+        writeln!(
+            f,
+            "\ncoverage: {} of {} cells executed ({:.1}%)",
+            quantity,
+            covered.len(),
+            100.0 * quantity as f64 / covered.len().max(1) as f64,
+        )?;
+
+        writeln!(f)?;
+        let listing = disassembly.to_string();
+        let mut items = disassembly.items.iter();
+        for line in listing.lines() {
+            if line.ends_with(':') {
+                writeln!(f, "{:>12}  {}", "", line)?;
+                continue;
+            }
+            let item = items.next().expect("one listing line per item");
+            let count = match profile.count(item.address()) {
+                0 if !covered.get(item.address()).cloned().unwrap_or_default() => "-".to_string(),
+                count => count.to_string(),
+            };
+            writeln!(f, "{:>12}  {}", count, line)?;
+        }
+        Ok(())
+    }
+}
+
+impl<M: Memory> Intcode<M> {
+    /// count the instructions executed at each address
+    pub fn with_profile(mut self) -> Self {
+        self.profile = Some(Profile::default());
+        self
+    }
+
+    /// the execution counts, if this machine is being profiled
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{asm::assemble, State};
+
+    #[test]
+    fn test_profile() {
+        let program = assemble(
+            "
+            loop:   add  [count], 1, [count]
+                    eq   [count], 3, [done]
+                    jf   [done], loop
+                    hlt
+                    out  [count]
+            count:  data 0
+            done:   data 0
+            ",
+        )
+        .unwrap();
+        let mut computer = Intcode::new(program.clone()).with_profile();
+        assert_eq!(computer.resume().unwrap(), State::Halted);
+        let profile = computer.profile().unwrap();
+
+        assert_eq!(profile.retired(), 10);
+        assert_eq!(profile.count(0), 3);
+        assert_eq!(profile.count(11), 1);
+        assert_eq!(profile.count(12), 0);
+        assert_eq!(profile.per_opcode()[&1], 3);
+        assert_eq!(profile.per_opcode()[&99], 1);
+        assert_eq!(profile.hot(2), vec![(0, 3), (4, 3)]);
+
+        let isa = computer.instruction_set();
+        let covered = profile.coverage(&program, isa);
+        assert!(covered[..12].iter().all(|&c| c));
+        assert!(!covered[12..].iter().any(|&c| c));
+
+        let report = profile.report(&program, isa, 1).to_string();
+        assert!(report.contains("instructions retired: 10\n"));
+        assert!(report.contains("coverage: 12 of 16 cells executed (75.0%)\n"));
+        assert!(report.contains("\n           -      12  data 4, 14, 0, 0\n"));
+        assert!(report.contains("\n           3       0  add  [14], 1, [14]\n"));
+        assert!(report.contains("\n             3       0  add  [14], 1, [14]\n"));
+    }
+}
@@ -0,0 +1,326 @@
+//! Search for the parameters which make an Intcode program reach a goal.
+//!
+//! Each parameter is either a memory cell patched before the program starts,
+//! or one of the program's inputs, and ranges over some values. Every
+//! combination of values is tried, in parallel, and each run which halts is
+//! judged by a goal predicate over its final memory and outputs. A search
+//! with more combinations than fit in a `u64` is rejected.
+//!
+//! Each run has an instruction budget, so a combination which sends the
+//! program into an infinite loop merely counts as exhausted.
+
+use super::{Intcode, IntcodeError, IntcodeMemory, State, Word};
+use rayon::prelude::*;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// default quantity of instructions each run may retire
+pub const DEFAULT_BUDGET: u64 = 1_000_000;
+
+/// Where a parameter's value goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Slot {
+    /// this memory cell
+    Cell(usize),
+    /// the input at this position, counting from 0
+    Input(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parameter {
+    pub slot: Slot,
+    pub values: RangeInclusive<Word>,
+}
+
+impl Parameter {
+    /// quantity of values, or `None` if there are more than fit in a `u64`
+    fn len(&self) -> Option<u64> {
+        if self.values.is_empty() {
+            return Some(0);
+        }
+        let span = *self.values.end() as i128 - *self.values.start() as i128 + 1;
+        u64::try_from(span).ok()
+    }
+}
+
+/// The search has more combinations than fit in a `u64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooLarge;
+
+impl fmt::Display for TooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "search has more than {} combinations", u64::MAX)
+    }
+}
+
+impl std::error::Error for TooLarge {}
+
+/// The end state of a run which halted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Finished<'a> {
+    pub memory: &'a [Word],
+    pub outputs: &'a [Word],
+}
+
+/// The result of a search.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Report {
+    /// the values of the parameters, in the order they were added, for each
+    /// run which met the goal; sorted
+    pub matches: Vec<Vec<Word>>,
+    /// quantity of runs which halted but missed the goal
+    pub misses: u64,
+    /// quantity of runs which used up their instruction budget
+    pub exhausted: u64,
+    /// quantity of runs which failed, or wanted more input than they were given
+    pub failed: u64,
+}
+
+impl Report {
+    fn merge(mut self, other: Report) -> Report {
+        self.matches.extend(other.matches);
+        self.misses += other.misses;
+        self.exhausted += other.exhausted;
+        self.failed += other.failed;
+        self
+    }
+}
+
+/// A search over the parameters of a program.
+#[derive(Debug, Clone)]
+pub struct Search<G> {
+    memory: IntcodeMemory,
+    inputs: Vec<Word>,
+    parameters: Vec<Parameter>,
+    budget: u64,
+    goal: G,
+}
+
+impl<G> Search<G>
+where
+    G: Fn(Finished) -> bool + Sync,
+{
+    pub fn new(memory: IntcodeMemory, goal: G) -> Self {
+        Search {
+            memory,
+            inputs: Vec::new(),
+            parameters: Vec::new(),
+            budget: DEFAULT_BUDGET,
+            goal,
+        }
+    }
+
+    /// give every run these inputs, except where parameters replace them
+    ///
+    /// Inputs which a parameter sets beyond the end of these are preceded by zeroes.
+    pub fn with_inputs(mut self, inputs: &[Word]) -> Self {
+        self.inputs = inputs.to_vec();
+        self
+    }
+
+    /// try each of `values` in memory cell `address`
+    pub fn with_cell(self, address: usize, values: RangeInclusive<Word>) -> Self {
+        assert!(
+            address < self.memory.len(),
+            "cell {} is beyond the program",
+            address
+        );
+        self.with_parameter(Slot::Cell(address), values)
+    }
+
+    /// try each of `values` as the input at `position`
+    pub fn with_input(self, position: usize, values: RangeInclusive<Word>) -> Self {
+        self.with_parameter(Slot::Input(position), values)
+    }
+
+    fn with_parameter(mut self, slot: Slot, values: RangeInclusive<Word>) -> Self {
+        assert!(
+            self.parameters
+                .iter()
+                .all(|parameter| parameter.slot != slot),
+            "{:?} is already a parameter",
+            slot
+        );
+        self.parameters.push(Parameter { slot, values });
+        self
+    }
+
+    /// allow each run to retire at most `budget` instructions
+    pub fn with_budget(mut self, budget: u64) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// quantity of combinations of parameter values, or `None` if there are
+    /// more than fit in a `u64`
+    pub fn size(&self) -> Option<u64> {
+        self.parameters
+            .iter()
+            .try_fold(1u64, |size, parameter| size.checked_mul(parameter.len()?))
+    }
+
+    /// the values of the parameters for combination `index`; the first parameter varies slowest
+    fn assignment(&self, mut index: u64) -> Vec<Word> {
+        let mut assignment = vec![0; self.parameters.len()];
+        for (value, parameter) in assignment.iter_mut().zip(&self.parameters).rev() {
+            // only called when the size fits, so each length does too
+            let len = parameter.len().unwrap();
+            // the true sum lies within the range, so wrapping gives it exactly
+            *value = parameter.values.start().wrapping_add((index % len) as Word);
+            index /= len;
+        }
+        assignment
+    }
+
+    /// run the program with these parameter values
+    fn attempt(&self, assignment: Vec<Word>) -> Report {
+        let mut memory = self.memory.clone();
+        let mut inputs = self.inputs.clone();
+        for (parameter, &value) in self.parameters.iter().zip(&assignment) {
+            match parameter.slot {
+                Slot::Cell(address) => memory[address] = value,
+                Slot::Input(position) => {
+                    if position >= inputs.len() {
+                        inputs.resize(position + 1, 0);
+                    }
+                    inputs[position] = value;
+                }
+            }
+        }
+
+        let mut computer = Intcode::new(memory).with_budget(self.budget);
+        for input in inputs {
+            computer.push_input(input);
+        }
+        let mut outputs = Vec::new();
+        let mut report = Report::default();
+        loop {
+            match computer.resume() {
+                Ok(State::Output(value)) => outputs.push(value),
+                Ok(State::Halted) => break,
+                Err(IntcodeError::BudgetExhausted { .. }) => {
+                    report.exhausted = 1;
+                    return report;
+                }
+                Ok(State::NeedsInput) | Err(_) => {
+                    report.failed = 1;
+                    return report;
+                }
+            }
+        }
+        let finished = Finished {
+            memory: computer.memory(),
+            outputs: &outputs,
+        };
+        if (self.goal)(finished) {
+            report.matches.push(assignment);
+        } else {
+            report.misses = 1;
+        }
+        report
+    }
+
+    /// try every combination of parameter values
+    pub fn run(&self) -> Result<Report, TooLarge> {
+        let mut report = (0..self.size().ok_or(TooLarge)?)
+            .into_par_iter()
+            .map(|index| self.attempt(self.assignment(index)))
+            .reduce(Report::default, Report::merge);
+        report.matches.sort();
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    #[test]
+    fn test_cells() {
+        // [0] = [a] * [b]
+        let memory = vec![2, 5, 6, 0, 99, 0, 0];
+        let report = Search::new(memory, |run: Finished| run.memory[0] == 12)
+            .with_cell(5, 1..=6)
+            .with_cell(6, 1..=6)
+            .run()
+            .unwrap();
+        assert_eq!(
+            report.matches,
+            vec![vec![2, 6], vec![3, 4], vec![4, 3], vec![6, 2]]
+        );
+        assert_eq!(report.misses, 32);
+    }
+
+    #[test]
+    fn test_inputs_and_budget() {
+        // output the first input, after counting down from the second
+        let memory = assemble(
+            "
+                    in   [x]
+                    in   [n]
+            loop:   add  [n], -1, [n]
+                    lt   [n], 1, [t]
+                    jf   [t], loop
+                    out  [x]
+                    hlt
+            x:      data 0
+            n:      data 0
+            t:      data 0
+            ",
+        )
+        .unwrap();
+        let search = Search::new(memory, |run: Finished| run.outputs == [7])
+            .with_inputs(&[7])
+            .with_input(1, 1..=100)
+            .with_budget(100);
+        assert_eq!(search.size(), Some(100));
+        let report = search.run().unwrap();
+        // each lap of the loop takes 3 instructions
+        assert_eq!(report.matches.len(), 32);
+        assert_eq!(report.matches[0], vec![1]);
+        assert_eq!(report.exhausted, 68);
+    }
+
+    #[test]
+    fn test_failures() {
+        // wants an input which isn't given
+        let memory = vec![3, 0, 99];
+        let report = Search::new(memory, |_: Finished| true)
+            .with_cell(2, 98..=99)
+            .run()
+            .unwrap();
+        assert_eq!(report.failed, 2);
+    }
+
+    #[test]
+    fn test_too_large() {
+        let memory = vec![99, 0, 0];
+        let search = Search::new(memory.clone(), |_: Finished| true);
+        let search = search.with_cell(1, Word::MIN + 1..=Word::MAX);
+        assert_eq!(search.size(), Some(u64::MAX));
+
+        let search =
+            Search::new(memory.clone(), |_: Finished| true).with_cell(1, Word::MIN..=Word::MAX);
+        assert_eq!(search.size(), None);
+        assert_eq!(search.run(), Err(TooLarge));
+
+        let search = Search::new(memory, |_: Finished| true)
+            .with_cell(1, 0..=1 << 40)
+            .with_cell(2, 0..=1 << 40);
+        assert_eq!(search.size(), None);
+        assert_eq!(search.run(), Err(TooLarge));
+    }
+
+    #[test]
+    fn test_extreme_values() {
+        let memory = vec![99, 0];
+        let report = Search::new(memory, |run: Finished| run.memory[1] < 0)
+            .with_cell(1, Word::MAX - 1..=Word::MAX)
+            .with_input(0, Word::MIN..=Word::MIN + 1)
+            .run()
+            .unwrap();
+        assert_eq!(report.misses, 4);
+    }
+}
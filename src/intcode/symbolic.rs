@@ -0,0 +1,782 @@
+//! Run Intcode programs on symbolic values, to find what they compute.
+//!
+//! Chosen memory cells hold named symbols instead of numbers, as do any
+//! inputs beyond those supplied. Arithmetic on symbols builds expression
+//! trees, folding constants as it goes. At the end, memory and outputs hold
+//! expressions in the symbols; those which only add and multiply can be
+//! flattened into polynomials, and linear ones solved for a target value.
+//!
+//! Control flow must not depend on symbols: a jump whose condition or target
+//! isn't constant, like a write to a symbolic address or a symbolic
+//! instruction word, ends the evaluation with an error. Reading a symbolic
+//! address is allowed, as the result may never be used; it produces an opaque
+//! `Load`. Only the standard instruction set is understood.
+
+use super::{Intcode, IntcodeError, IntcodeMemory, Mode, Word};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::ops::{Add, Mul, RangeInclusive};
+
+/// default quantity of instructions an evaluation may retire
+pub const DEFAULT_BUDGET: u64 = 1_000_000;
+
+/// maximum quantity of solutions `Polynomial::solve_linear` will list
+pub const MAX_SOLUTIONS: usize = 1 << 20;
+
+/// A value computed from symbols.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Const(Word),
+    Symbol(String),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    /// 1 if the first is less than the second, else 0
+    LessThan(Box<Expr>, Box<Expr>),
+    /// 1 if the operands are equal, else 0
+    Equals(Box<Expr>, Box<Expr>),
+    /// the contents of memory at a symbolic address
+    Load(Box<Expr>),
+}
+
+impl Add for Expr {
+    type Output = Expr;
+
+    fn add(self, other: Expr) -> Expr {
+        match (self, other) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(a.wrapping_add(b)),
+            (Expr::Const(0), x) | (x, Expr::Const(0)) => x,
+            (a, b) => Expr::Add(Box::new(a), Box::new(b)),
+        }
+    }
+}
+
+impl Mul for Expr {
+    type Output = Expr;
+
+    fn mul(self, other: Expr) -> Expr {
+        match (self, other) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(a.wrapping_mul(b)),
+            (Expr::Const(0), _) | (_, Expr::Const(0)) => Expr::Const(0),
+            (Expr::Const(1), x) | (x, Expr::Const(1)) => x,
+            (a, b) => Expr::Mul(Box::new(a), Box::new(b)),
+        }
+    }
+}
+
+impl Expr {
+    pub fn less_than(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const((a < b) as Word),
+            (a, b) => Expr::LessThan(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn equals(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const((a == b) as Word),
+            (a, b) if a == b => Expr::Const(1),
+            (a, b) => Expr::Equals(Box::new(a), Box::new(b)),
+        }
+    }
+
+    /// the value, if it doesn't depend on any symbol
+    pub fn constant(&self) -> Option<Word> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// flatten the expression into a polynomial, if it only adds and multiplies
+    pub fn polynomial(&self) -> Option<Polynomial> {
+        match self {
+            Expr::Const(value) => Some(Polynomial::constant(*value)),
+            Expr::Symbol(name) => Some(Polynomial::symbol(name)),
+            Expr::Add(a, b) => Some(&a.polynomial()? + &b.polynomial()?),
+            Expr::Mul(a, b) => Some(&a.polynomial()? * &b.polynomial()?),
+            Expr::LessThan(..) | Expr::Equals(..) | Expr::Load(..) => None,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Const(value) => write!(f, "{}", value),
+            Expr::Symbol(name) => write!(f, "{}", name),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Mul(a, b) => write!(f, "({} * {})", a, b),
+            Expr::LessThan(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Equals(a, b) => write!(f, "({} == {})", a, b),
+            Expr::Load(address) => write!(f, "[{}]", address),
+        }
+    }
+}
+
+/// A product of symbols, each raised to a power.
+type Monomial = BTreeMap<String, u32>;
+
+/// the product of two monomials: the sum of their powers
+fn multiply(a: &Monomial, b: &Monomial) -> Monomial {
+    let mut product = a.clone();
+    for (name, power) in b {
+        *product.entry(name.clone()).or_insert(0) += power;
+    }
+    product
+}
+
+/// A sum of monomials with integer coefficients.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Polynomial {
+    terms: BTreeMap<Monomial, Word>,
+}
+
+impl Polynomial {
+    pub fn constant(value: Word) -> Polynomial {
+        let mut polynomial = Polynomial::default();
+        polynomial.accumulate(Monomial::new(), value);
+        polynomial
+    }
+
+    pub fn symbol(name: &str) -> Polynomial {
+        let mut monomial = Monomial::new();
+        monomial.insert(name.to_string(), 1);
+        let mut polynomial = Polynomial::default();
+        polynomial.accumulate(monomial, 1);
+        polynomial
+    }
+
+    fn accumulate(&mut self, monomial: Monomial, coefficient: Word) {
+        let entry = self.terms.entry(monomial).or_insert(0);
+        *entry = entry.wrapping_add(coefficient);
+        self.terms.retain(|_, coefficient| *coefficient != 0);
+    }
+
+    /// the highest total power of any term
+    pub fn degree(&self) -> u32 {
+        self.terms
+            .keys()
+            .map(|monomial| monomial.values().sum())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// the coefficient of the term which is just `name`
+    pub fn coefficient(&self, name: &str) -> Word {
+        let mut monomial = Monomial::new();
+        monomial.insert(name.to_string(), 1);
+        self.terms.get(&monomial).cloned().unwrap_or(0)
+    }
+
+    /// the term which involves no symbols
+    pub fn constant_term(&self) -> Word {
+        self.terms.get(&Monomial::new()).cloned().unwrap_or(0)
+    }
+
+    /// evaluate with the given values for the symbols, or `None` if one is missing
+    pub fn evaluate(&self, values: &HashMap<&str, Word>) -> Option<Word> {
+        let mut sum: Word = 0;
+        for (monomial, &coefficient) in &self.terms {
+            let mut term = coefficient;
+            for (name, &power) in monomial {
+                let value = *values.get(name.as_str())?;
+                for _ in 0..power {
+                    term = term.wrapping_mul(value);
+                }
+            }
+            sum = sum.wrapping_add(term);
+        }
+        Some(sum)
+    }
+
+    /// every assignment of the bounded symbols for which this linear
+    /// polynomial equals `target`
+    ///
+    /// All symbols but the last are enumerated; the last is solved for.
+    /// Arithmetic wraps, as it does in the machine, so every value which
+    /// solves the equation modulo 2^64 is found.
+    /// Assignments are listed in the order of `bounds`. Returns `None` if
+    /// the polynomial isn't linear, involves a symbol without bounds, or has
+    /// more than `MAX_SOLUTIONS` solutions.
+    pub fn solve_linear(
+        &self,
+        target: Word,
+        bounds: &[(&str, RangeInclusive<Word>)],
+    ) -> Option<Vec<Vec<Word>>> {
+        if self.degree() > 1
+            || self
+                .terms
+                .keys()
+                .flat_map(BTreeMap::keys)
+                .any(|name| bounds.iter().all(|(bounded, _)| bounded != name))
+        {
+            return None;
+        }
+        let ((last, last_range), enumerated) = match bounds.split_last() {
+            Some(split) => split,
+            None => {
+                return Some(if self.constant_term() == target {
+                    vec![vec![]]
+                } else {
+                    vec![]
+                })
+            }
+        };
+        let divisor = self.coefficient(last);
+
+        let mut solutions = Vec::new();
+        let mut assignment: Vec<Word> =
+            enumerated.iter().map(|(_, range)| *range.start()).collect();
+        if enumerated.iter().any(|(_, range)| range.is_empty()) {
+            return Some(solutions);
+        }
+        loop {
+            let partial = enumerated.iter().zip(&assignment).fold(
+                self.constant_term(),
+                |sum, ((name, _), &value)| {
+                    sum.wrapping_add(self.coefficient(name).wrapping_mul(value))
+                },
+            );
+            let remainder = target.wrapping_sub(partial);
+            if let Some((first, step)) = congruence(divisor, remainder) {
+                // every value of the last symbol in range which is congruent to `first`
+                let (start, end) = (*last_range.start() as i128, *last_range.end() as i128);
+                let first = start + (first - start).rem_euclid(step);
+                if first <= end {
+                    let count = (end - first) / step + 1;
+                    if count > (MAX_SOLUTIONS - solutions.len()) as i128 {
+                        return None;
+                    }
+                    for idx in 0..count {
+                        let mut solution = assignment.clone();
+                        solution.push((first + idx * step) as Word);
+                        solutions.push(solution);
+                    }
+                }
+            }
+
+            // advance the enumerated symbols like an odometer
+            let mut idx = assignment.len();
+            loop {
+                if idx == 0 {
+                    return Some(solutions);
+                }
+                idx -= 1;
+                if assignment[idx] < *enumerated[idx].1.end() {
+                    assignment[idx] += 1;
+                    break;
+                }
+                assignment[idx] = *enumerated[idx].1.start();
+            }
+        }
+    }
+}
+
+/// the inverse of an odd number, modulo 2^64
+fn inverse(odd: u64) -> u64 {
+    // Newton's method: each step doubles the quantity of correct low bits,
+    // and odd * odd == 1 modulo 8 to begin with
+    let mut inverse = odd;
+    for _ in 0..5 {
+        inverse = inverse.wrapping_mul(2u64.wrapping_sub(odd.wrapping_mul(inverse)));
+    }
+    inverse
+}
+
+/// solve `coefficient * x == value` modulo 2^64
+///
+/// The solutions are those congruent to the first of the pair, modulo the
+/// second; there are none if this returns `None`.
+fn congruence(coefficient: Word, value: Word) -> Option<(i128, i128)> {
+    let (coefficient, value) = (coefficient as u64, value as u64);
+    // coefficient == 2^zeros * odd, so the solution is only fixed modulo 2^(64 - zeros)
+    let zeros = coefficient.trailing_zeros();
+    if zeros == 64 {
+        return if value == 0 { Some((0, 1)) } else { None };
+    }
+    if value.trailing_zeros() < zeros {
+        return None;
+    }
+    let step = 1i128 << (64 - zeros);
+    let first = (value >> zeros).wrapping_mul(inverse(coefficient >> zeros)) as i128;
+    Some((first.rem_euclid(step), step))
+}
+
+impl Add for &Polynomial {
+    type Output = Polynomial;
+
+    fn add(self, other: &Polynomial) -> Polynomial {
+        let mut sum = self.clone();
+        for (monomial, &coefficient) in &other.terms {
+            sum.accumulate(monomial.clone(), coefficient);
+        }
+        sum
+    }
+}
+
+impl Mul for &Polynomial {
+    type Output = Polynomial;
+
+    fn mul(self, other: &Polynomial) -> Polynomial {
+        let mut product = Polynomial::default();
+        for (a, &x) in &self.terms {
+            for (b, &y) in &other.terms {
+                product.accumulate(multiply(a, b), x.wrapping_mul(y));
+            }
+        }
+        product
+    }
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        // highest degree first, so the constant comes last
+        let mut terms: Vec<_> = self.terms.iter().collect();
+        terms.sort_by_key(|(monomial, _)| std::cmp::Reverse(monomial.values().sum::<u32>()));
+        for (idx, (monomial, &coefficient)) in terms.into_iter().enumerate() {
+            let magnitude = coefficient.unsigned_abs();
+            match (idx, coefficient < 0) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            let factors: Vec<String> = monomial
+                .iter()
+                .map(|(name, &power)| match power {
+                    1 => name.clone(),
+                    _ => format!("{}^{}", name, power),
+                })
+                .collect();
+            match (magnitude, factors.is_empty()) {
+                (_, true) => write!(f, "{}", magnitude)?,
+                (1, false) => write!(f, "{}", factors.join("*"))?,
+                (_, false) => write!(f, "{}*{}", magnitude, factors.join("*"))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why symbolic evaluation couldn't continue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolicError {
+    /// the instruction word at `ip` depends on a symbol
+    SymbolicInstruction { ip: usize, word: Expr },
+    /// the instruction at `ip` writes to, or adjusts the relative base by, a symbolic value
+    SymbolicAddress { ip: usize, address: Expr },
+    /// whether the jump at `ip` is taken, or where it goes, depends on a symbol
+    DataDependentJump {
+        ip: usize,
+        condition: Expr,
+        target: Expr,
+    },
+    /// the program fails regardless of the symbols' values
+    Machine(IntcodeError),
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SymbolicError::*;
+        match self {
+            SymbolicInstruction { ip, word } => {
+                write!(f, "symbolic instruction at ip {}: {}", ip, word)
+            }
+            SymbolicAddress { ip, address } => {
+                write!(f, "symbolic address at ip {}: {}", ip, address)
+            }
+            DataDependentJump {
+                ip,
+                condition,
+                target,
+            } => write!(
+                f,
+                "data-dependent jump at ip {}: if {} goto {}",
+                ip, condition, target
+            ),
+            Machine(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SymbolicError {}
+
+/// The end state of a program which halted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evaluation {
+    pub memory: Vec<Expr>,
+    pub outputs: Vec<Expr>,
+}
+
+/// An Intcode machine whose memory and inputs may hold symbols.
+#[derive(Debug, Clone)]
+pub struct Symbolic {
+    memory: Vec<Expr>,
+    ip: usize,
+    relative_base: Word,
+    inputs: VecDeque<Word>,
+    /// quantity of symbolic inputs consumed so far
+    symbolic_inputs: usize,
+    outputs: Vec<Expr>,
+    budget: u64,
+}
+
+impl Symbolic {
+    pub fn new(memory: &IntcodeMemory) -> Symbolic {
+        Symbolic {
+            memory: memory.iter().map(|&word| Expr::Const(word)).collect(),
+            ip: 0,
+            relative_base: 0,
+            inputs: VecDeque::new(),
+            symbolic_inputs: 0,
+            outputs: Vec::new(),
+            budget: DEFAULT_BUDGET,
+        }
+    }
+
+    /// replace the contents of `address` with the symbol `name`
+    pub fn with_symbol(mut self, address: usize, name: &str) -> Self {
+        if address >= self.memory.len() {
+            self.memory.resize(address + 1, Expr::Const(0));
+        }
+        self.memory[address] = Expr::Symbol(name.to_string());
+        self
+    }
+
+    /// supply concrete inputs; any further inputs are the symbols `in0`, `in1`, ...
+    pub fn with_inputs(mut self, inputs: &[Word]) -> Self {
+        self.inputs.extend(inputs);
+        self
+    }
+
+    /// allow at most `budget` instructions to retire
+    pub fn with_budget(mut self, budget: u64) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    fn load(&self, address: usize) -> Expr {
+        self.memory.get(address).cloned().unwrap_or(Expr::Const(0))
+    }
+
+    fn error(&self, make: impl FnOnce(usize, Word) -> IntcodeError) -> SymbolicError {
+        let word = self.load(self.ip).constant().unwrap_or_default();
+        SymbolicError::Machine(make(self.ip, word))
+    }
+
+    /// the address a parameter refers to, as an expression
+    fn address(&self, offset: usize, mode: Mode) -> Expr {
+        let param = self.load(self.ip + offset);
+        match mode {
+            Mode::Relative => Expr::Const(self.relative_base) + param,
+            _ => param,
+        }
+    }
+
+    fn operand(&self, offset: usize, mode: Mode) -> Result<Expr, SymbolicError> {
+        if mode == Mode::Immediate {
+            return Ok(self.load(self.ip + offset));
+        }
+        match self.address(offset, mode) {
+            Expr::Const(address) if address < 0 => {
+                Err(self.error(|ip, word| IntcodeError::NegativeAddress { ip, word, address }))
+            }
+            Expr::Const(address) => Ok(self.load(address as usize)),
+            address => Ok(Expr::Load(Box::new(address))),
+        }
+    }
+
+    fn store(&mut self, offset: usize, mode: Mode, value: Expr) -> Result<(), SymbolicError> {
+        let address = match (mode, self.address(offset, mode)) {
+            (Mode::Immediate, _) => {
+                return Err(self.error(|ip, word| IntcodeError::ImmediateWrite { ip, word }))
+            }
+            (_, Expr::Const(address)) if address < 0 => {
+                return Err(self.error(|ip, word| IntcodeError::NegativeAddress {
+                    ip,
+                    word,
+                    address,
+                }))
+            }
+            (_, Expr::Const(address)) => address as usize,
+            (_, address) => {
+                return Err(SymbolicError::SymbolicAddress {
+                    ip: self.ip,
+                    address,
+                })
+            }
+        };
+        if address >= self.memory.len() {
+            self.memory.resize(address + 1, Expr::Const(0));
+        }
+        self.memory[address] = value;
+        Ok(())
+    }
+
+    /// execute one instruction; whether the program halted
+    fn step(&mut self) -> Result<bool, SymbolicError> {
+        let word = match self.load(self.ip) {
+            Expr::Const(word) => word,
+            word => return Err(SymbolicError::SymbolicInstruction { ip: self.ip, word }),
+        };
+        let (opcode, m1, m2, m3) = Intcode::destructure(word).map_err(|err| match err {
+            IntcodeError::InvalidMode { .. } => {
+                self.error(|ip, word| IntcodeError::InvalidMode { ip, word })
+            }
+            _ => self.error(|ip, word| IntcodeError::InvalidOpcode { ip, word }),
+        })?;
+        match opcode {
+            1 | 2 | 7 | 8 => {
+                let (a, b) = (self.operand(1, m1)?, self.operand(2, m2)?);
+                let value = match opcode {
+                    1 => a + b,
+                    2 => a * b,
+                    7 => Expr::less_than(a, b),
+                    _ => Expr::equals(a, b),
+                };
+                self.store(3, m3, value)?;
+                self.ip += 4;
+            }
+            3 => {
+                let value = match self.inputs.pop_front() {
+                    Some(value) => Expr::Const(value),
+                    None => {
+                        self.symbolic_inputs += 1;
+                        Expr::Symbol(format!("in{}", self.symbolic_inputs - 1))
+                    }
+                };
+                self.store(1, m1, value)?;
+                self.ip += 2;
+            }
+            4 => {
+                let value = self.operand(1, m1)?;
+                self.outputs.push(value);
+                self.ip += 2;
+            }
+            5 | 6 => {
+                let (condition, target) = (self.operand(1, m1)?, self.operand(2, m2)?);
+                let jumps = match condition.constant() {
+                    Some(value) => (value != 0) == (opcode == 5),
+                    None => {
+                        return Err(SymbolicError::DataDependentJump {
+                            ip: self.ip,
+                            condition,
+                            target,
+                        })
+                    }
+                };
+                if !jumps {
+                    self.ip += 3;
+                    return Ok(false);
+                }
+                self.ip = match target.constant() {
+                    Some(target) if target < 0 => {
+                        return Err(self.error(|ip, word| IntcodeError::NegativeAddress {
+                            ip,
+                            word,
+                            address: target,
+                        }))
+                    }
+                    Some(target) => target as usize,
+                    None => {
+                        return Err(SymbolicError::DataDependentJump {
+                            ip: self.ip,
+                            condition,
+                            target,
+                        })
+                    }
+                };
+            }
+            9 => {
+                let offset = self.operand(1, m1)?;
+                match offset.constant() {
+                    Some(offset) => self.relative_base = self.relative_base.wrapping_add(offset),
+                    None => {
+                        return Err(SymbolicError::SymbolicAddress {
+                            ip: self.ip,
+                            address: offset,
+                        })
+                    }
+                }
+                self.ip += 2;
+            }
+            99 => return Ok(true),
+            _ => return Err(self.error(|ip, word| IntcodeError::InvalidOpcode { ip, word })),
+        }
+        Ok(false)
+    }
+
+    /// run the program until it halts
+    pub fn run(mut self) -> Result<Evaluation, SymbolicError> {
+        for _ in 0..self.budget {
+            if self.ip >= self.memory.len() {
+                return Err(SymbolicError::Machine(IntcodeError::IpOverran {
+                    ip: self.ip,
+                }));
+            }
+            if self.step()? {
+                return Ok(Evaluation {
+                    memory: self.memory,
+                    outputs: self.outputs,
+                });
+            }
+        }
+        Err(SymbolicError::Machine(IntcodeError::BudgetExhausted {
+            ip: self.ip,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// like day 2: [0] = 5 * noun + verb + 7, after a first instruction
+    /// which reads through the noun and verb and whose result is discarded
+    fn gravity() -> IntcodeMemory {
+        vec![
+            1, 0, 0, 3, 1002, 1, 5, 17, 1, 17, 2, 0, 1001, 0, 7, 0, 99, 0,
+        ]
+    }
+
+    #[test]
+    fn test_linear() {
+        let evaluation = Symbolic::new(&gravity())
+            .with_symbol(1, "noun")
+            .with_symbol(2, "verb")
+            .run()
+            .unwrap();
+        assert_eq!(evaluation.memory[3].to_string(), "([noun] + [verb])");
+        let polynomial = evaluation.memory[0].polynomial().unwrap();
+        assert_eq!(polynomial.to_string(), "5*noun + verb + 7");
+
+        let solutions = polynomial
+            .solve_linear(101, &[("noun", 0..=99), ("verb", 0..=99)])
+            .unwrap();
+        assert_eq!(solutions.len(), 19);
+        assert!(solutions.contains(&vec![12, 34]));
+        for solution in solutions {
+            let values = vec![("noun", solution[0]), ("verb", solution[1])]
+                .into_iter()
+                .collect();
+            assert_eq!(polynomial.evaluate(&values), Some(101));
+        }
+    }
+
+    #[test]
+    fn test_solve_wraps() {
+        // -x == MIN has the solution MIN, as -MIN wraps around to MIN
+        let polynomial = &Polynomial::symbol("x") * &Polynomial::constant(-1);
+        assert_eq!(
+            polynomial.solve_linear(Word::MIN, &[("x", Word::MIN..=Word::MIN + 1)]),
+            Some(vec![vec![Word::MIN]])
+        );
+    }
+
+    #[test]
+    fn test_solve_modular() {
+        // 2x == -2 is solved by -1 and by MAX, as 2 * MAX wraps around to -2
+        let polynomial = &Polynomial::symbol("x") * &Polynomial::constant(2);
+        assert_eq!(
+            polynomial.solve_linear(-2, &[("x", 0..=Word::MAX)]),
+            Some(vec![vec![Word::MAX]])
+        );
+        assert_eq!(
+            polynomial.solve_linear(-2, &[("x", Word::MIN..=Word::MAX)]),
+            Some(vec![vec![-1], vec![Word::MAX]])
+        );
+        // an even coefficient never produces an odd value
+        assert_eq!(
+            polynomial.solve_linear(3, &[("x", Word::MIN..=Word::MAX)]),
+            Some(vec![])
+        );
+        // 3x == 1 is solved by the inverse of 3
+        let polynomial = &Polynomial::symbol("x") * &Polynomial::constant(3);
+        let solutions = polynomial
+            .solve_linear(1, &[("x", Word::MIN..=Word::MAX)])
+            .unwrap();
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0][0].wrapping_mul(3), 1);
+        // each solution of 12x + y == 40 is checked by evaluation
+        let polynomial =
+            &(&Polynomial::symbol("x") * &Polynomial::constant(12)) + &Polynomial::symbol("y");
+        let solutions = polynomial
+            .solve_linear(40, &[("y", -4..=4), ("x", Word::MIN..=Word::MAX)])
+            .unwrap();
+        // y must be a multiple of 4, leaving four solutions for x each time
+        assert_eq!(solutions.len(), 3 * 4);
+        for solution in solutions {
+            let values = vec![("y", solution[0]), ("x", solution[1])]
+                .into_iter()
+                .collect();
+            assert_eq!(polynomial.evaluate(&values), Some(40));
+        }
+    }
+
+    #[test]
+    fn test_free_symbol() {
+        // x doesn't affect y + 1, so every x in range solves it
+        let polynomial = &Polynomial::symbol("y") + &Polynomial::constant(1);
+        assert_eq!(
+            polynomial.solve_linear(2, &[("y", 0..=3), ("x", 5..=7)]),
+            Some(vec![vec![1, 5], vec![1, 6], vec![1, 7]])
+        );
+        // too many to list
+        assert_eq!(
+            polynomial.solve_linear(2, &[("y", 0..=3), ("x", Word::MIN..=Word::MAX)]),
+            None
+        );
+        assert_eq!(
+            polynomial.solve_linear(2, &[("y", 0..=3), ("x", 0..=1 << 40)]),
+            None
+        );
+        // no solutions, however many candidates
+        assert_eq!(
+            polynomial.solve_linear(0, &[("y", 0..=3), ("x", Word::MIN..=Word::MAX)]),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn test_inputs() {
+        // output (in0 + 2) * in1
+        let memory = vec![
+            3, 17, 3, 18, 1001, 17, 2, 19, 2, 19, 18, 19, 4, 19, 99, 0, 0, 0, 0, 0,
+        ];
+        let evaluation = Symbolic::new(&memory).run().unwrap();
+        let polynomial = evaluation.outputs[0].polynomial().unwrap();
+        assert_eq!(polynomial.to_string(), "in0*in1 + 2*in1");
+        assert_eq!(polynomial.degree(), 2);
+
+        let evaluation = Symbolic::new(&memory).with_inputs(&[3]).run().unwrap();
+        assert_eq!(evaluation.outputs[0].to_string(), "(5 * in0)");
+    }
+
+    #[test]
+    fn test_data_dependent_jump() {
+        let memory = vec![3, 9, 1005, 9, 7, 104, 0, 99, 0, 0];
+        assert_eq!(
+            Symbolic::new(&memory).run(),
+            Err(SymbolicError::DataDependentJump {
+                ip: 2,
+                condition: Expr::Symbol("in0".into()),
+                target: Expr::Const(7),
+            })
+        );
+        // with a concrete input, the jump resolves
+        let evaluation = Symbolic::new(&memory).with_inputs(&[0]).run().unwrap();
+        assert_eq!(evaluation.outputs, vec![Expr::Const(0)]);
+    }
+
+    #[test]
+    fn test_symbolic_write() {
+        let memory = vec![1101, 1, 1, 0, 99];
+        assert!(matches!(
+            Symbolic::new(&memory).with_symbol(3, "x").run(),
+            Err(SymbolicError::SymbolicAddress { ip: 0, .. })
+        ));
+    }
+}
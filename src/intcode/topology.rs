@@ -0,0 +1,271 @@
+//! Wire Intcode machines together and run them until nothing more can happen.
+//!
+//! A topology is a set of named machines, each with its own initial inputs,
+//! and a set of edges, each of which carries one machine's outputs to
+//! another's input queue. A machine may feed several others, and edges may
+//! form cycles, as in an amplifier chain with a feedback loop.
+//!
+//! Machines run in turn, in the order they were declared, until every
+//! machine has halted or is waiting for input which will never arrive.
+//! Everything happens in one thread, so a topology always runs the same way.
+
+use super::{Intcode, IntcodeError, IntcodeMemory, State, Word};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+#[derive(Debug, Clone)]
+struct Machine {
+    name: String,
+    computer: Intcode,
+    /// whether the machine stopped for want of input
+    blocked: bool,
+}
+
+/// A set of named Intcode machines and the edges between them.
+#[derive(Debug, Clone, Default)]
+pub struct Topology {
+    machines: Vec<Machine>,
+    indices: HashMap<String, usize>,
+    /// `(from, to)` machine indices
+    edges: Vec<(usize, usize)>,
+}
+
+/// What happened when a topology ran.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Report {
+    /// machines in the order in which they halted
+    pub halted: Vec<String>,
+    /// the last value each machine output
+    pub outputs: BTreeMap<String, Word>,
+    /// the last value carried by each edge, by the names of its machines
+    pub edges: BTreeMap<(String, String), Word>,
+}
+
+impl Report {
+    /// the machine which halted last, if any halted
+    pub fn last_halted(&self) -> Option<&str> {
+        self.halted.last().map(String::as_str)
+    }
+
+    /// the last value output by the named machine
+    pub fn output(&self, machine: &str) -> Option<Word> {
+        self.outputs.get(machine).cloned()
+    }
+
+    /// the last value carried from one named machine to another
+    pub fn edge(&self, from: &str, to: &str) -> Option<Word> {
+        self.edges.get(&(from.to_string(), to.to_string())).cloned()
+    }
+}
+
+/// A machine in a topology failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopologyError {
+    pub machine: String,
+    pub error: IntcodeError,
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.machine, self.error)
+    }
+}
+
+impl std::error::Error for TopologyError {}
+
+impl Topology {
+    pub fn new() -> Topology {
+        Topology::default()
+    }
+
+    /// add a machine running `memory`, which receives `inputs` before anything else
+    pub fn with_machine(self, name: &str, memory: IntcodeMemory, inputs: &[Word]) -> Self {
+        let mut computer = Intcode::new(memory);
+        for &input in inputs {
+            computer.push_input(input);
+        }
+        self.with_computer(name, computer)
+    }
+
+    /// add a machine which has already been configured
+    pub fn with_computer(mut self, name: &str, computer: Intcode) -> Self {
+        assert!(
+            !self.indices.contains_key(name),
+            "{} is already a machine",
+            name
+        );
+        self.indices.insert(name.to_string(), self.machines.len());
+        self.machines.push(Machine {
+            name: name.to_string(),
+            computer,
+            blocked: false,
+        });
+        self
+    }
+
+    fn index(&self, name: &str) -> usize {
+        *self
+            .indices
+            .get(name)
+            .unwrap_or_else(|| panic!("{} is not a machine", name))
+    }
+
+    /// carry every output of `from` to the input of `to`
+    pub fn with_edge(mut self, from: &str, to: &str) -> Self {
+        let edge = (self.index(from), self.index(to));
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+        self
+    }
+
+    pub fn computer(&self, name: &str) -> &Intcode {
+        &self.machines[self.index(name)].computer
+    }
+
+    /// whether the machine can make progress
+    fn is_runnable(&self, idx: usize) -> bool {
+        let machine = &self.machines[idx];
+        !machine.computer.is_halted()
+            && (!machine.blocked || !machine.computer.pending_inputs().is_empty())
+    }
+
+    /// run the machine at `idx` until it halts or needs input, returning its outputs
+    fn run_machine(&mut self, idx: usize) -> Result<(Vec<Word>, bool), TopologyError> {
+        let machine = &mut self.machines[idx];
+        machine.blocked = false;
+        let mut outputs = Vec::new();
+        loop {
+            let state = machine.computer.resume().map_err(|error| TopologyError {
+                machine: machine.name.clone(),
+                error,
+            })?;
+            match state {
+                State::Output(value) => outputs.push(value),
+                State::NeedsInput => {
+                    machine.blocked = true;
+                    return Ok((outputs, false));
+                }
+                State::Halted => return Ok((outputs, true)),
+            }
+        }
+    }
+
+    /// run every machine until all have halted or are waiting for input
+    pub fn run(&mut self) -> Result<Report, TopologyError> {
+        let mut report = Report::default();
+        let mut progressed = true;
+        while progressed {
+            progressed = false;
+            for idx in 0..self.machines.len() {
+                if !self.is_runnable(idx) {
+                    continue;
+                }
+                progressed = true;
+                let (outputs, halted) = self.run_machine(idx)?;
+                let name = self.machines[idx].name.clone();
+                if halted {
+                    report.halted.push(name.clone());
+                }
+                let last = match outputs.last() {
+                    Some(&last) => last,
+                    None => continue,
+                };
+                report.outputs.insert(name.clone(), last);
+                for &(from, to) in &self.edges {
+                    if from != idx {
+                        continue;
+                    }
+                    let key = (name.clone(), self.machines[to].name.clone());
+                    report.edges.insert(key, last);
+                    for &value in &outputs {
+                        self.machines[to].computer.push_input(value);
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    /// read a phase, then output each input plus the phase until a zero arrives
+    fn adder() -> IntcodeMemory {
+        assemble(
+            "
+                    in   [phase]
+            loop:   in   [x]
+                    jf   [x], done
+                    add  [x], [phase], [x]
+                    out  [x]
+                    jt   1, loop
+            done:   hlt
+            phase:  data 0
+            x:      data 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let mut topology = Topology::new()
+            .with_machine("source", adder(), &[1, 10, 20, 0])
+            .with_machine("left", adder(), &[100])
+            .with_machine("right", adder(), &[1000])
+            .with_edge("source", "left")
+            .with_edge("source", "right");
+        let report = topology.run().unwrap();
+        assert_eq!(report.edge("source", "left"), Some(21));
+        assert_eq!(report.edge("source", "right"), Some(21));
+        assert_eq!(report.output("left"), Some(121));
+        assert_eq!(report.output("right"), Some(1021));
+        // neither consumer ever sees a zero, so only the source halts
+        assert_eq!(report.halted, vec!["source"]);
+        assert_eq!(topology.computer("left").pending_inputs().len(), 0);
+    }
+
+    #[test]
+    fn test_feedback() {
+        // the signal circulates, growing by 3 per lap, until it exceeds 20
+        let limiter = assemble(
+            "
+            loop:   in   [x]
+                    lt   20, [x], [big]
+                    jt   [big], done
+                    out  [x]
+                    jt   1, loop
+            done:   out  0
+                    hlt
+            x:      data 0
+            big:    data 0
+            ",
+        )
+        .unwrap();
+        let mut topology = Topology::new()
+            .with_machine("a", adder(), &[1, 1])
+            .with_machine("b", adder(), &[2])
+            .with_computer("limit", Intcode::new(limiter))
+            .with_edge("a", "b")
+            .with_edge("b", "limit")
+            .with_edge("limit", "a");
+        let report = topology.run().unwrap();
+        // b never sees a zero, so it waits forever
+        assert_eq!(report.halted, vec!["limit", "a"]);
+        assert_eq!(report.last_halted(), Some("a"));
+        assert_eq!(report.edge("b", "limit"), Some(22));
+        assert_eq!(report.edge("limit", "a"), Some(0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_unknown_machine() {
+        Topology::new()
+            .with_machine("a", adder(), &[])
+            .with_edge("a", "b");
+    }
+}
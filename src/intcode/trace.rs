@@ -0,0 +1,298 @@
+//! Record the instructions an Intcode machine executes, and compare recordings.
+//!
+//! Traces are stored as JSON lines, one event per retired instruction:
+//!
+//! ```text
+//! {"ip":4,"opcode":1,"operands":[2,3],"write":[16,5]}
+//! ```
+//!
+//! `operands` holds the values of the instruction's read parameters, after
+//! resolving their modes. `write` holds the address and new value of the cell
+//! written by the instruction, or `null` if it wrote nothing.
+
+use super::{Intcode, IntcodeWord, Memory, Opcode, Word};
+use crossbeam_channel::Sender;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::VecDeque;
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+/// A single retired instruction.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TraceEvent<W = Word> {
+    pub ip: usize,
+    pub opcode: Opcode,
+    pub operands: Vec<W>,
+    pub write: Option<(usize, W)>,
+}
+
+impl<W: IntcodeWord> TraceEvent<W> {
+    /// record the instruction at the computer's ip, before it executes
+    ///
+    /// Returns `None` if the instruction can't execute.
+    pub(super) fn before<M: Memory<Word = W>>(computer: &Intcode<M>) -> Option<TraceEvent<W>> {
+        let (opcode, p1, p2, p3) = computer.decode().ok()?;
+        let mnemonic = computer.instructions.by_opcode(opcode)?;
+        let modes = [p1, p2, p3];
+        let operands = (0..mnemonic.arity)
+            .filter(|&idx| Some(idx) != mnemonic.writes)
+            .map(|idx| computer.mem(idx + 1, modes[idx]).ok())
+            .collect::<Option<Vec<_>>>()?;
+        let write = computer
+            .accesses()
+            .ok()?
+            .write
+            .map(|address| (address, W::default()));
+        Some(TraceEvent {
+            ip: computer.ip,
+            opcode,
+            operands,
+            write,
+        })
+    }
+
+    /// complete the event once the instruction has retired
+    pub(super) fn after<M: Memory<Word = W>>(mut self, computer: &Intcode<M>) -> TraceEvent<W> {
+        if let Some((address, value)) = &mut self.write {
+            *value = computer.memory.read(*address);
+        }
+        self
+    }
+}
+
+impl<W: fmt::Display> fmt::Display for TraceEvent<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operands = self
+            .operands
+            .iter()
+            .map(W::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(
+            f,
+            r#"{{"ip":{},"opcode":{},"operands":[{}],"write":"#,
+            self.ip, self.opcode, operands
+        )?;
+        match &self.write {
+            Some((address, value)) => write!(f, "[{},{}]}}", address, value),
+            None => write!(f, "null}}"),
+        }
+    }
+}
+
+lazy_static! {
+    static ref EVENT_RE: Regex = Regex::new(
+        r#"^\{\s*"ip"\s*:\s*(?P<ip>\d+)\s*,\s*"opcode"\s*:\s*(?P<opcode>\d+)\s*,\s*"operands"\s*:\s*\[(?P<operands>[-\d,\s]*)\]\s*,\s*"write"\s*:\s*(null|\[\s*(?P<address>\d+)\s*,\s*(?P<value>-?\d+)\s*\])\s*\}$"#
+    )
+    .unwrap();
+}
+
+impl<W: FromStr> FromStr for TraceEvent<W> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let captures = EVENT_RE
+            .captures(s)
+            .ok_or_else(|| format!("not a trace event: {}", s))?;
+        let field = |name: &str| captures.name(name).map(|m| m.as_str());
+        let parse_err = |name: &'static str| move |err: ParseIntError| format!("{}: {}", name, err);
+        let word_err = |name: &'static str| move |_| format!("{}: invalid word", name);
+        let operands = field("operands").unwrap_or_default().trim();
+        let operands = if operands.is_empty() {
+            Vec::new()
+        } else {
+            operands
+                .split(',')
+                .map(|operand| operand.trim().parse())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(word_err("operands"))?
+        };
+        let write = match (field("address"), field("value")) {
+            (Some(address), Some(value)) => Some((
+                address.parse().map_err(parse_err("address"))?,
+                value.parse().map_err(word_err("value"))?,
+            )),
+            _ => None,
+        };
+        Ok(TraceEvent {
+            ip: field("ip").unwrap().parse().map_err(parse_err("ip"))?,
+            opcode: field("opcode")
+                .unwrap()
+                .parse()
+                .map_err(parse_err("opcode"))?,
+            operands,
+            write,
+        })
+    }
+}
+
+impl<M: Memory> Intcode<M> {
+    /// send an event for every retired instruction
+    pub fn with_trace(mut self, trace: Sender<TraceEvent<M::Word>>) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+}
+
+/// The first point at which two traces differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence<W = Word> {
+    /// index of the first differing event
+    pub index: usize,
+    /// the events immediately preceding the divergence, which both traces share
+    pub context: Vec<TraceEvent<W>>,
+    /// the differing events; `None` if that trace ended first
+    pub left: Option<TraceEvent<W>>,
+    pub right: Option<TraceEvent<W>>,
+}
+
+impl<W: fmt::Display> fmt::Display for Divergence<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "traces diverge at event {}", self.index)?;
+        let first = self.index - self.context.len();
+        for (idx, event) in self.context.iter().enumerate() {
+            writeln!(f, "  {:>8}  {}", first + idx, event)?;
+        }
+        for (marker, event) in &[("<", &self.left), (">", &self.right)] {
+            match event {
+                Some(event) => writeln!(f, "{} {:>8}  {}", marker, self.index, event)?,
+                None => writeln!(f, "{} {:>8}  (end of trace)", marker, self.index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// find the first divergence between two traces, keeping up to `context`
+/// of the preceding shared events
+///
+/// Returns `None` if the traces are identical.
+pub fn diff<W, L, R>(left: L, right: R, context: usize) -> Option<Divergence<W>>
+where
+    W: PartialEq,
+    L: IntoIterator<Item = TraceEvent<W>>,
+    R: IntoIterator<Item = TraceEvent<W>>,
+{
+    let mut left = left.into_iter();
+    let mut right = right.into_iter();
+    let mut shared = VecDeque::with_capacity(context + 1);
+    let mut index = 0;
+    loop {
+        match (left.next(), right.next()) {
+            (None, None) => return None,
+            (Some(l), Some(r)) if l == r => {
+                shared.push_back(l);
+                if shared.len() > context {
+                    shared.pop_front();
+                }
+                index += 1;
+            }
+            (left, right) => {
+                return Some(Divergence {
+                    index,
+                    context: shared.into_iter().collect(),
+                    left,
+                    right,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{asm::assemble, channel, IntcodeMemory};
+
+    fn record(memory: IntcodeMemory, inputs: &[Word]) -> Vec<TraceEvent> {
+        let (sender, receiver) = channel();
+        let mut computer = Intcode::new(memory).with_trace(sender);
+        for &input in inputs {
+            computer.push_input(input);
+        }
+        while computer.resume().unwrap() != crate::intcode::State::Halted {}
+        receiver.try_iter().collect()
+    }
+
+    fn program() -> IntcodeMemory {
+        assemble(
+            "
+                    in   [x]
+                    jf   [x], zero
+                    out  1
+                    hlt
+            zero:   out  0
+                    hlt
+            x:      data 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_record() {
+        let trace = record(program(), &[5]);
+        let expect = vec![
+            TraceEvent {
+                ip: 0,
+                opcode: 3,
+                operands: vec![],
+                write: Some((11, 5)),
+            },
+            TraceEvent {
+                ip: 2,
+                opcode: 6,
+                operands: vec![5, 8],
+                write: None,
+            },
+            TraceEvent {
+                ip: 5,
+                opcode: 4,
+                operands: vec![1],
+                write: None,
+            },
+            TraceEvent {
+                ip: 7,
+                opcode: 99,
+                operands: vec![],
+                write: None,
+            },
+        ];
+        assert_eq!(trace, expect);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for event in record(program(), &[-3]) {
+            let line = event.to_string();
+            assert_eq!(line.parse::<TraceEvent>(), Ok(event), "{}", line);
+        }
+        let event: TraceEvent = r#"{"ip":2,"opcode":6,"operands":[-5,8],"write":null}"#
+            .parse()
+            .unwrap();
+        assert_eq!(event.operands, vec![-5, 8]);
+    }
+
+    #[test]
+    fn test_diff() {
+        let positive = record(program(), &[5]);
+        let negative = record(program(), &[-5]);
+        let zero = record(program(), &[0]);
+
+        assert_eq!(diff(positive.clone(), positive.clone(), 3), None);
+
+        let divergence = diff(positive.clone(), negative, 3).unwrap();
+        assert_eq!(divergence.index, 0);
+        assert!(divergence.context.is_empty());
+
+        let divergence = diff(zero, positive.clone(), 3).unwrap();
+        assert_eq!(divergence.index, 0);
+
+        let divergence = diff(positive.clone(), positive[..2].to_vec(), 1).unwrap();
+        assert_eq!(divergence.index, 2);
+        assert_eq!(divergence.context, vec![positive[1].clone()]);
+        assert_eq!(divergence.right, None);
+    }
+}
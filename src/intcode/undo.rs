@@ -0,0 +1,251 @@
+//! Reverse execution for Intcode machines.
+//!
+//! When a machine has an undo log, every retired instruction records just
+//! enough to reverse it: the ip, relative base and halt flag beforehand, the
+//! previous value of any memory cell it wrote, and any input it consumed.
+//! The log is a ring buffer: once it is full, the oldest entries are
+//! forgotten, so memory overhead is bounded by its capacity.
+//!
+//! Stepping backwards can't recall outputs which were already sent to an
+//! output stream; they are sent again if execution passes them again.
+//! Inputs which are stepped back over are returned to the front of the
+//! pending input queue, so replaying the program reproduces the same run.
+
+use super::{Intcode, IntcodeWord, Memory, Opcode, Word};
+use std::collections::VecDeque;
+
+/// Input consumed or output produced by an instruction, as its handler's
+/// effect reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Io<W = Word> {
+    Input(W),
+    Output(W),
+}
+
+/// Everything required to reverse a single instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoEntry<W = Word> {
+    /// address of the instruction
+    pub ip: usize,
+    pub opcode: Opcode,
+    pub relative_base_offset: W,
+    pub halted: bool,
+    /// length of memory before the instruction, which may have grown it
+    pub memory_len: usize,
+    /// address written by the instruction, and the value it held beforehand
+    pub write: Option<(usize, W)>,
+    /// input consumed or output produced by the instruction
+    pub io: Option<Io<W>>,
+}
+
+impl<W: IntcodeWord> UndoEntry<W> {
+    /// record the state which the instruction at the computer's ip may change
+    pub(super) fn before<M: Memory<Word = W>>(computer: &Intcode<M>) -> UndoEntry<W> {
+        let write = computer
+            .accesses()
+            .ok()
+            .and_then(|accesses| accesses.write)
+            .map(|address| (address, computer.memory.read(address)));
+        UndoEntry {
+            ip: computer.ip,
+            opcode: computer.opcode(),
+            relative_base_offset: computer.relative_base_offset.clone(),
+            halted: computer.halted,
+            memory_len: computer.memory.len(),
+            write,
+            io: None,
+        }
+    }
+
+    /// complete the entry once the instruction has retired
+    pub(super) fn after<M: Memory<Word = W>>(mut self, computer: &Intcode<M>) -> UndoEntry<W> {
+        self.io = computer.io.clone();
+        self
+    }
+}
+
+/// A bounded history of retired instructions.
+#[derive(Debug, Clone, Default)]
+pub struct UndoLog<W = Word> {
+    capacity: usize,
+    entries: VecDeque<UndoEntry<W>>,
+}
+
+impl<W> UndoLog<W> {
+    pub fn new(capacity: usize) -> UndoLog<W> {
+        UndoLog {
+            capacity,
+            entries: VecDeque::with_capacity(capacity.min(4096)),
+        }
+    }
+
+    /// the maximum quantity of instructions which can be undone
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// iterate over the recorded instructions, from oldest to most recent
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &UndoEntry<W>> {
+        self.entries.iter()
+    }
+
+    pub(super) fn push(&mut self, entry: UndoEntry<W>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl<M: Memory> Intcode<M> {
+    /// record up to `capacity` retired instructions, so they can be undone
+    pub fn with_undo_log(mut self, capacity: usize) -> Self {
+        self.undo_log = Some(UndoLog::new(capacity));
+        self
+    }
+
+    /// the history of instructions which can be undone, if one is being kept
+    pub fn undo_log(&self) -> Option<&UndoLog<M::Word>> {
+        self.undo_log.as_ref()
+    }
+
+    /// reverse the most recently retired instruction
+    ///
+    /// Returns the undone entry, or `None` if there is no history to undo.
+    /// The instruction no longer counts as retired, so it doesn't use up the
+    /// budget when it runs again.
+    pub fn step_back(&mut self) -> Option<UndoEntry<M::Word>> {
+        let entry = self.undo_log.as_mut()?.entries.pop_back()?;
+        self.retired = self.retired.saturating_sub(1);
+        self.ip = entry.ip;
+        self.relative_base_offset = entry.relative_base_offset.clone();
+        self.halted = entry.halted;
+        if let Some((address, old)) = &entry.write {
+            self.memory.write(*address, old.clone());
+        }
+        self.memory.truncate(entry.memory_len);
+        if let Some(cache) = &mut self.decode_cache {
+            cache.clear();
+        }
+        if let Some(Io::Input(input)) = &entry.io {
+            self.pending_inputs.push_front(input.clone());
+        }
+        Some(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{asm::assemble, State};
+
+    #[test]
+    fn test_step_back_restores_state() {
+        let program = assemble(
+            "
+                    in   [20]
+                    arb  5
+                    mul  [20], 3, [rb+20]
+                    out  [rb+20]
+                    hlt
+            ",
+        )
+        .unwrap();
+        let mut computer = Intcode::new(program).with_undo_log(16).with_budget(100);
+        computer.push_input(7);
+        let initial = computer.snapshot();
+
+        let mut states = vec![computer.snapshot()];
+        while computer.step().unwrap() != Some(State::Halted) {
+            states.push(computer.snapshot());
+        }
+        // the halt itself is undoable
+        states.push(computer.snapshot());
+        assert_eq!(computer.undo_log().unwrap().len(), states.len() - 1);
+
+        assert_eq!(computer.retired(), states.len() as u64 - 1);
+
+        states.pop();
+        while let Some(expect) = states.pop() {
+            assert!(computer.step_back().is_some());
+            assert_eq!(computer.snapshot(), expect);
+            assert_eq!(computer.retired(), states.len() as u64);
+            assert_eq!(computer.remaining_budget(), Some(100 - states.len() as u64));
+        }
+        assert!(computer.step_back().is_none());
+        assert_eq!(computer.snapshot(), initial);
+        assert_eq!(computer.retired(), 0);
+    }
+
+    #[test]
+    fn test_renumbered_io() {
+        use crate::intcode::isa::{Effect, Flow, InstructionSet, Mnemonic};
+        let io = |name, opcode, writes| Mnemonic {
+            name,
+            opcode,
+            arity: 1,
+            writes,
+            flow: Flow::Next,
+        };
+        let isa = InstructionSet::standard()
+            .without(3)
+            .without(4)
+            .with(io("in", 13, Some(0)), |_, _| Ok(Effect::Input))
+            .with(io("out", 14, None), |p, _| Ok(Effect::Output(p[0])))
+            // writes like the standard input instruction, but consumes nothing
+            .with(io("one", 3, Some(0)), |_, _| Ok(Effect::Store(1)));
+
+        let mut computer = Intcode::new(vec![13, 9, 3, 10, 14, 9, 99, 0, 0, 0, 0])
+            .with_instruction_set(isa)
+            .with_undo_log(16);
+        computer.push_input(5);
+        assert_eq!(computer.resume(), Ok(State::Output(5)));
+        let entries: Vec<_> = computer.undo_log().unwrap().iter().cloned().collect();
+        assert_eq!(entries[0].io, Some(Io::Input(5)));
+        assert_eq!(entries[1].io, None);
+        assert_eq!(entries[2].io, Some(Io::Output(5)));
+
+        for _ in 0..3 {
+            computer.step_back().unwrap();
+        }
+        assert_eq!(computer.pending_inputs(), &[5]);
+        assert_eq!(computer.resume(), Ok(State::Output(5)));
+    }
+
+    #[test]
+    fn test_capacity_bounds_history() {
+        let program = assemble(
+            "
+            loop:   add  [count], 1, [count]
+                    jt   1, loop
+            count:  data 0
+            ",
+        )
+        .unwrap();
+        let mut computer = Intcode::new(program).with_undo_log(10);
+        for _ in 0..100 {
+            computer.step().unwrap();
+        }
+        assert_eq!(computer.undo_log().unwrap().len(), 10);
+        for _ in 0..10 {
+            assert!(computer.step_back().is_some());
+        }
+        assert!(computer.step_back().is_none());
+        assert_eq!(computer.memory()[7], 45);
+    }
+}
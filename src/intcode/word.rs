@@ -0,0 +1,109 @@
+//! Value types for Intcode memory, and how arithmetic on them behaves.
+//!
+//! Puzzle programs expect signed 64-bit values, so `Word` (`i64`) is the
+//! default. Arithmetic on it wraps on overflow unless the machine is set to
+//! `Arithmetic::Checked`, in which case overflow is an error. Programs which
+//! genuinely need huge values can run on `BigInt` words, which never overflow.
+
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+use std::fmt;
+use std::str::FromStr;
+
+/// A value which an Intcode machine can store and compute with.
+///
+/// Instruction words, addresses and relative base adjustments must fit in an
+/// `i64` to be meaningful; other values may be as large as the type allows.
+pub trait IntcodeWord:
+    Clone + Default + fmt::Debug + fmt::Display + FromStr + Ord + From<i64> + Send + 'static
+{
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+    fn wrapping_add(&self, other: &Self) -> Self;
+    fn wrapping_mul(&self, other: &Self) -> Self;
+
+    /// this value as an `i64`, if it fits
+    fn to_i64(&self) -> Option<i64>;
+
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl IntcodeWord for i64 {
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        i64::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        i64::checked_mul(*self, *other)
+    }
+
+    fn wrapping_add(&self, other: &Self) -> Self {
+        i64::wrapping_add(*self, *other)
+    }
+
+    fn wrapping_mul(&self, other: &Self) -> Self {
+        i64::wrapping_mul(*self, *other)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(*self)
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+}
+
+impl IntcodeWord for BigInt {
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn wrapping_add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn wrapping_mul(&self, other: &Self) -> Self {
+        self * other
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        ToPrimitive::to_i64(self)
+    }
+
+    fn is_zero(&self) -> bool {
+        Zero::is_zero(self)
+    }
+}
+
+/// What `add` and `mul` do when their result doesn't fit in a word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arithmetic {
+    /// wrap around, as two's complement hardware would
+    #[default]
+    Wrapping,
+    /// fail with `IntcodeError::Overflow`
+    Checked,
+}
+
+impl Arithmetic {
+    pub fn add<W: IntcodeWord>(self, a: &W, b: &W) -> Option<W> {
+        match self {
+            Arithmetic::Wrapping => Some(a.wrapping_add(b)),
+            Arithmetic::Checked => a.checked_add(b),
+        }
+    }
+
+    pub fn mul<W: IntcodeWord>(self, a: &W, b: &W) -> Option<W> {
+        match self {
+            Arithmetic::Wrapping => Some(a.wrapping_mul(b)),
+            Arithmetic::Checked => a.checked_mul(b),
+        }
+    }
+}
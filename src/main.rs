@@ -1,6 +1,25 @@
-use aoc2019::dispatch;
+use aoc2019::{
+    dispatch,
+    intcode::{
+        aot::translate,
+        cfg::control_flow,
+        channel,
+        console::Console,
+        debugger::Debugger,
+        disasm::disassemble,
+        fuzz::Fuzzer,
+        lint::lint,
+        trace::{diff, TraceEvent},
+        Intcode, IntcodeMemory, State, Word,
+    },
+    parse, CommaSep,
+};
 use chrono::{Datelike, Utc};
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -8,7 +27,7 @@ use structopt::StructOpt;
 struct Opt {
     /// input file
     #[structopt(parse(from_os_str))]
-    input: PathBuf,
+    input: Option<PathBuf>,
 
     /// day (default: today's date)
     #[structopt(short, long)]
@@ -21,14 +40,437 @@ struct Opt {
     /// run part 2
     #[structopt(long)]
     part2: bool,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// disassemble an intcode program
+    Disasm {
+        /// intcode program
+        #[structopt(parse(from_os_str))]
+        program: PathBuf,
+    },
+    /// print an intcode program's control-flow graph in Graphviz DOT format
+    Cfg {
+        /// intcode program
+        #[structopt(parse(from_os_str))]
+        program: PathBuf,
+    },
+    /// talk to an ASCII intcode program from the terminal
+    Console {
+        /// intcode program
+        #[structopt(parse(from_os_str))]
+        program: PathBuf,
+
+        /// file of lines to send before reading from stdin (may be repeated)
+        #[structopt(short, long = "script", number_of_values = 1, parse(from_os_str))]
+        scripts: Vec<PathBuf>,
+
+        /// file to which the session is logged
+        #[structopt(short, long, parse(from_os_str))]
+        transcript: Option<PathBuf>,
+    },
+    /// debug an intcode program interactively
+    Debug {
+        /// intcode program
+        #[structopt(parse(from_os_str))]
+        program: PathBuf,
+
+        /// queue an input for the program (may be repeated)
+        #[structopt(
+            short,
+            long = "input",
+            number_of_values = 1,
+            allow_hyphen_values = true
+        )]
+        inputs: Vec<Word>,
+
+        /// quantity of instructions which can be stepped backwards
+        #[structopt(long, default_value = "100000")]
+        history: usize,
+    },
+    /// check an intcode program for problems without running it
+    Lint {
+        /// intcode program
+        #[structopt(parse(from_os_str))]
+        program: PathBuf,
+    },
+    /// run random and mutated intcode programs, saving minimal reproducers of failures
+    Fuzz {
+        /// programs to mutate
+        #[structopt(parse(from_os_str))]
+        corpus: Vec<PathBuf>,
+
+        /// quantity of cases to run
+        #[structopt(short = "n", long, default_value = "100000")]
+        iterations: u64,
+
+        /// seed for the random generator
+        #[structopt(short, long, default_value = "0")]
+        seed: u64,
+
+        /// quantity of instructions each case may retire
+        #[structopt(short, long, default_value = "10000")]
+        budget: u64,
+
+        /// count errors as failures, not just panics
+        #[structopt(short, long)]
+        errors: bool,
+
+        /// directory in which reproducers are saved
+        #[structopt(short, long, default_value = "fuzz", parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// run an intcode program, recording each instruction it executes
+    Trace {
+        /// intcode program
+        #[structopt(parse(from_os_str))]
+        program: PathBuf,
+
+        /// file to which the trace is written as JSON lines
+        #[structopt(parse(from_os_str))]
+        output: PathBuf,
+
+        /// queue an input for the program (may be repeated)
+        #[structopt(
+            short,
+            long = "input",
+            number_of_values = 1,
+            allow_hyphen_values = true
+        )]
+        inputs: Vec<Word>,
+    },
+    /// run an intcode program, then report where it spent its time
+    Profile {
+        /// intcode program
+        #[structopt(parse(from_os_str))]
+        program: PathBuf,
+
+        /// queue an input for the program (may be repeated)
+        #[structopt(
+            short,
+            long = "input",
+            number_of_values = 1,
+            allow_hyphen_values = true
+        )]
+        inputs: Vec<Word>,
+
+        /// quantity of hot instructions to list
+        #[structopt(short, long, default_value = "10")]
+        top: usize,
+    },
+    /// translate an intcode program into a Rust module
+    Translate {
+        /// intcode program
+        #[structopt(parse(from_os_str))]
+        program: PathBuf,
+
+        /// name of the type implementing `Translated`
+        #[structopt(short, long, default_value = "Program")]
+        name: String,
+
+        /// path by which the generated code refers to the intcode module
+        #[structopt(long, default_value = "aoc2019::intcode")]
+        intcode_path: String,
+
+        /// file to write the module to (default: stdout)
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// find the first point at which two traces differ
+    TraceDiff {
+        #[structopt(parse(from_os_str))]
+        left: PathBuf,
+
+        #[structopt(parse(from_os_str))]
+        right: PathBuf,
+
+        /// quantity of shared events to show before the divergence
+        #[structopt(short, long, default_value = "5")]
+        context: usize,
+    },
+}
+
+fn load_program(path: &Path) -> Option<IntcodeMemory> {
+    match parse::<CommaSep<Word>>(path) {
+        Ok(lines) => Some(lines.flatten().collect()),
+        Err(err) => {
+            println!("could not read {}: {}", path.to_string_lossy(), err);
+            None
+        }
+    }
+}
+
+/// talk to `memory` over stdin and stdout
+fn console(
+    memory: IntcodeMemory,
+    scripts: &[PathBuf],
+    transcript: Option<&Path>,
+) -> Result<(), String> {
+    let mut console = Console::new(Intcode::new(memory));
+    for path in scripts {
+        let script = std::fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {}", path.to_string_lossy(), err))?;
+        console = console.with_script(script.lines().map(str::to_string).collect::<Vec<_>>());
+    }
+    if let Some(path) = transcript {
+        let file = File::create(path)
+            .map_err(|err| format!("could not create {}: {}", path.to_string_lossy(), err))?;
+        console = console.with_transcript(Box::new(BufWriter::new(file)));
+    }
+    let stdin = std::io::stdin();
+    console
+        .run(stdin.lock(), std::io::stdout())
+        .map_err(|err| err.to_string())
+}
+
+/// fuzz the machine, printing a summary and saving a reproducer of each failure in `output`
+fn fuzz(fuzzer: &mut Fuzzer, iterations: u64, output: &Path) -> Result<(), String> {
+    // panics are expected, and summarized below
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let report = fuzzer.run(iterations);
+    std::panic::set_hook(hook);
+    println!("{} cases", report.runs);
+    for (class, count) in &report.classes {
+        println!("{:>10}  {}", count, class);
+    }
+    for finding in &report.findings {
+        println!();
+        println!("{}", finding.verdict);
+        println!("  minimal: {}", finding.minimal);
+        let written = finding
+            .minimal
+            .save(output, &finding.verdict.class())
+            .map_err(|err| format!("could not save to {}: {}", output.to_string_lossy(), err))?;
+        for path in written {
+            println!("  saved {}", path.to_string_lossy());
+        }
+    }
+    Ok(())
+}
+
+/// run `memory` to completion, printing its outputs and writing its trace to `path`
+fn record_trace(memory: IntcodeMemory, inputs: &[Word], path: &Path) -> Result<(), String> {
+    let file = File::create(path)
+        .map_err(|err| format!("could not create {}: {}", path.to_string_lossy(), err))?;
+    let mut file = BufWriter::new(file);
+    let (sender, receiver) = channel();
+    let mut computer = Intcode::new(memory).with_trace(sender);
+    for &input in inputs {
+        computer.push_input(input);
+    }
+    loop {
+        let state = computer.resume();
+        for event in receiver.try_iter() {
+            writeln!(file, "{}", event).map_err(|err| err.to_string())?;
+        }
+        match state.map_err(|err| err.to_string())? {
+            State::Output(value) => println!("{}", value),
+            State::NeedsInput => return Err("program needs more input".to_string()),
+            State::Halted => return file.flush().map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// read the trace events in `path`, one per line
+///
+/// Reading stops at the first line which can't be read or parsed, leaving a
+/// description of the problem, with its line number, in `error`.
+fn read_trace(
+    path: &Path,
+    error: Rc<RefCell<Option<String>>>,
+) -> Result<impl Iterator<Item = TraceEvent>, String> {
+    let name = path.to_string_lossy().into_owned();
+    let file = File::open(path).map_err(|err| format!("could not read {}: {}", name, err))?;
+    Ok(BufReader::new(file)
+        .lines()
+        .enumerate()
+        .map_while(move |(idx, line)| {
+            let event = line
+                .map_err(|err| err.to_string())
+                .and_then(|line| line.parse::<TraceEvent>());
+            match event {
+                Ok(event) => Some(event),
+                Err(err) => {
+                    *error.borrow_mut() = Some(format!("{}:{}: {}", name, idx + 1, err));
+                    None
+                }
+            }
+        }))
+}
+
+/// print the first point at which two traces differ
+fn trace_diff(left: &Path, right: &Path, context: usize) -> Result<(), String> {
+    let left_error = Rc::default();
+    let right_error = Rc::default();
+    let divergence = diff(
+        read_trace(left, Rc::clone(&left_error))?,
+        read_trace(right, Rc::clone(&right_error))?,
+        context,
+    );
+    // a trace which couldn't be read would otherwise look like it ended early
+    for error in [&left_error, &right_error].iter() {
+        if let Some(err) = error.borrow_mut().take() {
+            return Err(format!("could not parse trace: {}", err));
+        }
+    }
+    match divergence {
+        Some(divergence) => print!("{}", divergence),
+        None => println!("traces are identical"),
+    }
+    Ok(())
+}
+
+/// run `memory` to completion, printing its outputs and then its profile
+fn profile(memory: IntcodeMemory, inputs: &[Word], top: usize) -> Result<(), String> {
+    let mut computer = Intcode::new(memory.clone()).with_profile();
+    for &input in inputs {
+        computer.push_input(input);
+    }
+    loop {
+        match computer.resume().map_err(|err| err.to_string())? {
+            State::Output(value) => println!("{}", value),
+            State::NeedsInput => return Err("program needs more input".to_string()),
+            State::Halted => break,
+        }
+    }
+    let isa = computer.instruction_set();
+    print!("{}", computer.profile().unwrap().report(&memory, isa, top));
+    Ok(())
 }
 
 fn main() {
     let opt = Opt::from_args();
-    dispatch(
-        opt.day.unwrap_or_else(|| Utc::now().day() as u8),
-        &opt.input,
-        !opt.no_part1,
-        opt.part2,
-    )
+    match opt.command {
+        Some(Command::Disasm { program }) => {
+            if let Some(memory) = load_program(&program) {
+                print!("{}", disassemble(&memory));
+            }
+        }
+        Some(Command::Cfg { program }) => {
+            if let Some(memory) = load_program(&program) {
+                print!("{}", control_flow(&memory).dot());
+            }
+        }
+        Some(Command::Console {
+            program,
+            scripts,
+            transcript,
+        }) => {
+            if let Some(memory) = load_program(&program) {
+                if let Err(err) = console(memory, &scripts, transcript.as_deref()) {
+                    println!("{}", err);
+                }
+            }
+        }
+        Some(Command::Debug {
+            program,
+            inputs,
+            history,
+        }) => {
+            if let Some(memory) = load_program(&program) {
+                let mut computer = Intcode::new(memory).with_undo_log(history);
+                for input in inputs {
+                    computer.push_input(input);
+                }
+                let stdin = std::io::stdin();
+                Debugger::new(computer)
+                    .repl(stdin.lock(), std::io::stdout())
+                    .unwrap();
+            }
+        }
+        Some(Command::Lint { program }) => {
+            if let Some(memory) = load_program(&program) {
+                let diagnostics = lint(&memory);
+                if diagnostics.is_empty() {
+                    println!("no problems found");
+                }
+                for diagnostic in diagnostics {
+                    println!("{}", diagnostic);
+                }
+            }
+        }
+        Some(Command::Fuzz {
+            corpus,
+            iterations,
+            seed,
+            budget,
+            errors,
+            output,
+        }) => {
+            let programs: Option<Vec<IntcodeMemory>> =
+                corpus.iter().map(|path| load_program(path)).collect();
+            if let Some(programs) = programs {
+                let mut fuzzer = Fuzzer::new(seed)
+                    .with_corpus(programs)
+                    .with_budget(budget)
+                    .with_errors(errors);
+                if let Err(err) = fuzz(&mut fuzzer, iterations, &output) {
+                    println!("{}", err);
+                }
+            }
+        }
+        Some(Command::Trace {
+            program,
+            output,
+            inputs,
+        }) => {
+            if let Some(memory) = load_program(&program) {
+                if let Err(err) = record_trace(memory, &inputs, &output) {
+                    println!("{}", err);
+                }
+            }
+        }
+        Some(Command::Profile {
+            program,
+            inputs,
+            top,
+        }) => {
+            if let Some(memory) = load_program(&program) {
+                if let Err(err) = profile(memory, &inputs, top) {
+                    println!("{}", err);
+                }
+            }
+        }
+        Some(Command::Translate {
+            program,
+            name,
+            intcode_path,
+            output,
+        }) => {
+            if let Some(memory) = load_program(&program) {
+                let source = translate(&memory, &name, &intcode_path);
+                match output {
+                    Some(output) => {
+                        if let Err(err) = std::fs::write(&output, source) {
+                            println!("could not write {}: {}", output.to_string_lossy(), err);
+                        }
+                    }
+                    None => print!("{}", source),
+                }
+            }
+        }
+        Some(Command::TraceDiff {
+            left,
+            right,
+            context,
+        }) => {
+            if let Err(err) = trace_diff(&left, &right, context) {
+                println!("{}", err);
+            }
+        }
+        None => match opt.input {
+            Some(input) => dispatch(
+                opt.day.unwrap_or_else(|| Utc::now().day() as u8),
+                &input,
+                !opt.no_part1,
+                opt.part2,
+            ),
+            None => Opt::clap().print_help().unwrap(),
+        },
+    }
 }